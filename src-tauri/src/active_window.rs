@@ -0,0 +1,346 @@
+use crate::config::QuestionTemplate;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Terminal emulators, where Ctrl+C interrupts the running program instead of copying
+const TERMINALS: &[&str] = &[
+    "alacritty",
+    "conhost",
+    "cool-retro-term",
+    "foot",
+    "ghostty",
+    "gnome-terminal",
+    "gnome-terminal-server",
+    "guake",
+    "hyper",
+    "kgx",
+    "kitty",
+    "konsole",
+    "lxterminal",
+    "mate-terminal",
+    "mintty",
+    "openconsole",
+    "ptyxis",
+    "qterminal",
+    "rio",
+    "st",
+    "tabby",
+    "terminator",
+    "terminology",
+    "tilda",
+    "tilix",
+    "urxvt",
+    "warp",
+    "wezterm",
+    "wezterm-gui",
+    "windowsterminal",
+    "xfce4-terminal",
+    "xterm",
+    "yakuake",
+];
+
+/// The application that had focus when a hotkey fired
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ActiveApp {
+    pub app_name: String,
+    pub title: String,
+    pub process_path: String,
+}
+
+/// Captures the foreground window using the platform API
+pub async fn capture_active_window() -> Result<ActiveApp> {
+    // Run in blocking task since get_active_window() is synchronous
+    let window = tokio::task::spawn_blocking(active_win_pos_rs::get_active_window)
+        .await?
+        .map_err(|_| anyhow!("Failed to get the active window"))?;
+
+    Ok(ActiveApp {
+        app_name: window.app_name,
+        title: window.title,
+        process_path: window.process_path.to_string_lossy().to_string(),
+    })
+}
+
+/// Picks the template to run for the focused application.
+/// Templates whose include rules match win over catch-all templates without include rules.
+pub fn select_template<'a>(
+    templates: &'a [QuestionTemplate],
+    app: &ActiveApp,
+) -> Option<&'a QuestionTemplate> {
+    let allowed =
+        |t: &&QuestionTemplate| !t.exclude_apps.iter().any(|rule| matches_rule(rule, app));
+
+    templates
+        .iter()
+        .filter(allowed)
+        .find(|t| t.include_apps.iter().any(|rule| matches_rule(rule, app)))
+        .or_else(|| {
+            templates
+                .iter()
+                .filter(allowed)
+                .find(|t| t.include_apps.iter().all(|rule| rule.trim().is_empty()))
+        })
+}
+
+/// Matches a rule against the app, case-insensitively.
+/// `title:` rules match part of the window title, other rules match the whole app or
+/// executable name, where `*` stands for any run of characters.
+fn matches_rule(rule: &str, app: &ActiveApp) -> bool {
+    let rule = rule.trim().to_lowercase();
+    if rule.is_empty() {
+        return false;
+    }
+
+    if let Some(title_rule) = rule.strip_prefix("title:") {
+        return app.title.to_lowercase().contains(title_rule.trim());
+    }
+
+    glob_match(&rule, &app.app_name.to_lowercase()) || glob_match(&rule, &executable_name(app))
+}
+
+/// Whether the whole text matches the pattern, `*` matching any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*`, so the pattern has to be the whole text
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Whether the app is a terminal emulator, judged by its executable or app name
+pub fn is_terminal(app: &ActiveApp) -> bool {
+    let executable = executable_name(app);
+    let app_name = app.app_name.to_lowercase();
+
+    TERMINALS
+        .iter()
+        .any(|terminal| executable == *terminal || app_name == *terminal)
+        || app_name.contains("terminal")
+}
+
+/// Lowercase executable name without its extension
+fn executable_name(app: &ActiveApp) -> String {
+    // Windows paths keep their backslashes when parsed elsewhere
+    let file_name = app
+        .process_path
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default();
+
+    std::path::Path::new(file_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Fills the `{app_name}` and `{window_title}` placeholders of a template prompt
+pub fn apply_placeholders(prompt: &str, app: &ActiveApp) -> String {
+    prompt
+        .replace("{app_name}", &app.app_name)
+        .replace("{window_title}", &app.title)
+}
+
+/// Builds the system prompt describing where the text was captured
+pub fn context_message(app: &ActiveApp) -> Option<String> {
+    let location = match (app.app_name.is_empty(), app.title.is_empty()) {
+        (true, true) => return None,
+        (false, true) => format!("in the application \"{}\"", app.app_name),
+        (true, false) => format!("in a window titled \"{}\"", app.title),
+        (false, false) => format!(
+            "in the application \"{}\", in the window titled \"{}\"",
+            app.app_name, app.title
+        ),
+    };
+
+    Some(format!(
+        "The user is working {}. Use this as context for the request.",
+        location
+    ))
+}
+
+/// A popup prompt with its placeholders filled, and the window context to send along
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptContext {
+    pub prompt: String,
+    pub system_message: Option<String>,
+}
+
+/// Prepares a prompt typed or picked in the popup for the captured window
+pub fn prompt_context(prompt: &str, app: &ActiveApp, include_context: bool) -> PromptContext {
+    PromptContext {
+        prompt: apply_placeholders(prompt, app),
+        system_message: include_context.then(|| context_message(app)).flatten(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(id: &str, include: &[&str], exclude: &[&str]) -> QuestionTemplate {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id,
+            "prompt": "",
+            "include_apps": include,
+            "exclude_apps": exclude,
+        }))
+        .unwrap()
+    }
+
+    fn app(app_name: &str, title: &str, process_path: &str) -> ActiveApp {
+        ActiveApp {
+            app_name: app_name.to_string(),
+            title: title.to_string(),
+            process_path: process_path.to_string(),
+        }
+    }
+
+    fn selected(templates: &[QuestionTemplate], app: &ActiveApp) -> Option<String> {
+        select_template(templates, app).map(|t| t.id.clone())
+    }
+
+    #[test]
+    fn rules_match_case_insensitively() {
+        let editor = app(
+            "Visual Studio Code",
+            "main.rs — GitHub",
+            "/usr/share/code/code",
+        );
+
+        assert!(matches_rule("visual studio CODE", &editor));
+        assert!(matches_rule("CODE", &editor));
+        assert!(!matches_rule("studio", &editor));
+        assert!(!matches_rule("cod", &editor));
+        assert!(matches_rule("Title: github", &editor));
+        assert!(!matches_rule("title:slack", &editor));
+        assert!(!matches_rule("  ", &editor));
+    }
+
+    #[test]
+    fn rules_support_globs() {
+        let editor = app("Visual Studio Code", "", r"C:\Tools\Code.exe");
+
+        assert!(matches_rule("visual studio*", &editor));
+        assert!(matches_rule("*studio*", &editor));
+        assert!(matches_rule("c*e", &editor));
+        assert!(matches_rule("*", &editor));
+        assert!(!matches_rule("*studio", &editor));
+        assert!(!matches_rule("code*x", &editor));
+        assert!(!matches_rule("v*code*code", &editor));
+    }
+
+    #[test]
+    fn detects_terminals() {
+        assert!(is_terminal(&app("kitty", "~", "/usr/bin/kitty")));
+        assert!(is_terminal(&app(
+            "Windows Terminal",
+            "PowerShell",
+            r"C:\Program Files\WindowsApps\WindowsTerminal.exe"
+        )));
+        assert!(is_terminal(&app("Terminal", "bash", "")));
+        assert!(!is_terminal(&app("Firefox", "st", "/usr/bin/firefox")));
+        assert!(!is_terminal(&app("Code", "", "/usr/bin/code")));
+    }
+
+    #[test]
+    fn exclude_rules_win_over_include_rules() {
+        let templates = vec![
+            template("editor", &["code"], &["title:secret"]),
+            template("global", &[], &[]),
+        ];
+
+        let editor = app("Code", "notes.md", "/usr/bin/code");
+        let secret = app("Code", "secret.env", "/usr/bin/code");
+
+        assert_eq!(selected(&templates, &editor).as_deref(), Some("editor"));
+        assert_eq!(selected(&templates, &secret).as_deref(), Some("global"));
+    }
+
+    #[test]
+    fn empty_include_lists_match_every_app() {
+        let templates = vec![template("any", &[" "], &["slack"])];
+
+        assert_eq!(
+            selected(&templates, &app("Firefox", "", "")).as_deref(),
+            Some("any")
+        );
+        assert_eq!(selected(&templates, &app("Slack", "", "")), None);
+    }
+
+    #[test]
+    fn fills_known_placeholders_only() {
+        let browser = app("Firefox", "Docs", "");
+
+        assert_eq!(
+            apply_placeholders(
+                "In {app_name} ({window_title}): {selection} {APP_NAME}",
+                &browser
+            ),
+            "In Firefox (Docs): {selection} {APP_NAME}"
+        );
+        assert_eq!(
+            apply_placeholders("{window_title}", &ActiveApp::default()),
+            ""
+        );
+    }
+
+    #[test]
+    fn context_message_skips_missing_parts() {
+        assert_eq!(
+            context_message(&app("Slack", "", "")).as_deref(),
+            Some("The user is working in the application \"Slack\". Use this as context for the request.")
+        );
+        assert_eq!(
+            context_message(&app("", "Inbox", "")).as_deref(),
+            Some("The user is working in a window titled \"Inbox\". Use this as context for the request.")
+        );
+        assert_eq!(context_message(&ActiveApp::default()), None);
+    }
+
+    #[test]
+    fn prompt_context_only_describes_the_window_when_enabled() {
+        let editor = app("Code", "main.rs", "");
+
+        let context = prompt_context("Review {window_title}", &editor, false);
+        assert_eq!(context.prompt, "Review main.rs");
+        assert_eq!(context.system_message, None);
+
+        let context = prompt_context("Review", &editor, true);
+        assert_eq!(
+            context.system_message.as_deref(),
+            Some("The user is working in the application \"Code\", in the window titled \"main.rs\". Use this as context for the request.")
+        );
+    }
+
+    #[test]
+    fn matching_templates_win_over_the_global_template() {
+        let templates = vec![
+            template("global", &[], &[]),
+            template("terminal", &["kitty", "alacritty"], &[]),
+        ];
+
+        assert_eq!(
+            selected(&templates, &app("kitty", "", "")).as_deref(),
+            Some("terminal")
+        );
+        assert_eq!(
+            selected(&templates, &app("Firefox", "", "")).as_deref(),
+            Some("global")
+        );
+        assert_eq!(selected(&templates[1..], &app("Firefox", "", "")), None);
+    }
+}
@@ -0,0 +1,523 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+/// A mark drawn onto a screenshot before it is sent.
+/// Coordinates are logical pixels relative to the top-left of the selected region.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Annotation {
+    /// Outlined rectangle
+    Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        #[serde(default = "default_color")]
+        color: String,
+        #[serde(default = "default_thickness")]
+        thickness: f64,
+    },
+    /// Line with an arrowhead at its end
+    Arrow {
+        from_x: f64,
+        from_y: f64,
+        to_x: f64,
+        to_y: f64,
+        #[serde(default = "default_color")]
+        color: String,
+        #[serde(default = "default_thickness")]
+        thickness: f64,
+    },
+    /// Label on a light background, `y` is the top of the first line
+    Text {
+        x: f64,
+        y: f64,
+        text: String,
+        #[serde(default = "default_color")]
+        color: String,
+        #[serde(default = "default_text_size")]
+        size: f64,
+    },
+    /// Pixelated area
+    Blur {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    /// Area filled with solid black
+    Redact {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+}
+
+fn default_color() -> String {
+    "#ff3b30".to_string()
+}
+
+fn default_thickness() -> f64 {
+    3.0
+}
+
+fn default_text_size() -> f64 {
+    16.0
+}
+
+/// Draws the annotations onto the image. `scale` converts logical to image pixels.
+pub fn apply(image: &mut RgbaImage, annotations: &[Annotation], scale: f64) {
+    for annotation in annotations {
+        match annotation {
+            Annotation::Rect {
+                x,
+                y,
+                width,
+                height,
+                color,
+                thickness,
+            } => {
+                let t = (thickness * scale).round().max(1.0) as i64;
+                let (x0, y0) = ((x * scale).round() as i64, (y * scale).round() as i64);
+                let x1 = ((x + width) * scale).round() as i64;
+                let y1 = ((y + height) * scale).round() as i64;
+                let color = parse_color(color);
+
+                fill_rect(image, x0, y0, x1, y0 + t, color);
+                fill_rect(image, x0, y1 - t, x1, y1, color);
+                fill_rect(image, x0, y0, x0 + t, y1, color);
+                fill_rect(image, x1 - t, y0, x1, y1, color);
+            }
+            Annotation::Arrow {
+                from_x,
+                from_y,
+                to_x,
+                to_y,
+                color,
+                thickness,
+            } => draw_arrow(
+                image,
+                (from_x * scale, from_y * scale),
+                (to_x * scale, to_y * scale),
+                (thickness * scale).max(1.0),
+                parse_color(color),
+            ),
+            Annotation::Text {
+                x,
+                y,
+                text,
+                color,
+                size,
+            } => draw_text(
+                image,
+                (x * scale, y * scale),
+                text,
+                size * scale,
+                parse_color(color),
+            ),
+            Annotation::Blur {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let block = (12.0 * scale).round().max(4.0) as u32;
+                pixelate(
+                    image,
+                    (x * scale).round() as i64,
+                    (y * scale).round() as i64,
+                    (width * scale).round() as i64,
+                    (height * scale).round() as i64,
+                    block,
+                );
+            }
+            Annotation::Redact {
+                x,
+                y,
+                width,
+                height,
+            } => fill_rect(
+                image,
+                (x * scale).round() as i64,
+                (y * scale).round() as i64,
+                ((x + width) * scale).round() as i64,
+                ((y + height) * scale).round() as i64,
+                Rgba([0, 0, 0, 255]),
+            ),
+        }
+    }
+}
+
+/// Replaces an area with blocks of its average color, clamped to the image bounds
+pub fn pixelate(image: &mut RgbaImage, x: i64, y: i64, width: i64, height: i64, block: u32) {
+    let (x0, y0, x1, y1) = clamp_rect(image, x, y, x + width, y + height);
+    let block = block.max(1);
+
+    for by in (y0..y1).step_by(block as usize) {
+        for bx in (x0..x1).step_by(block as usize) {
+            let bx1 = (bx + block).min(x1);
+            let by1 = (by + block).min(y1);
+
+            let mut sum = [0u64; 4];
+            for py in by..by1 {
+                for px in bx..bx1 {
+                    for (total, channel) in sum.iter_mut().zip(image.get_pixel(px, py).0) {
+                        *total += channel as u64;
+                    }
+                }
+            }
+
+            let count = ((bx1 - bx) * (by1 - by)) as u64;
+            let average = Rgba(sum.map(|total| (total / count) as u8));
+            for py in by..by1 {
+                for px in bx..bx1 {
+                    image.put_pixel(px, py, average);
+                }
+            }
+        }
+    }
+}
+
+/// Parses `#rgb` or `#rrggbb`, falling back to the default red
+fn parse_color(color: &str) -> Rgba<u8> {
+    let hex = color.trim().trim_start_matches('#');
+    let expanded: String = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        _ => hex.to_string(),
+    };
+
+    match u32::from_str_radix(&expanded, 16) {
+        Ok(value) if expanded.len() == 6 => {
+            Rgba([(value >> 16) as u8, (value >> 8) as u8, value as u8, 255])
+        }
+        _ => Rgba([255, 59, 48, 255]),
+    }
+}
+
+/// Clamps a rectangle given by its corners to the image
+fn clamp_rect(image: &RgbaImage, x0: i64, y0: i64, x1: i64, y1: i64) -> (u32, u32, u32, u32) {
+    let clamp_x = |x: i64| x.clamp(0, image.width() as i64) as u32;
+    let clamp_y = |y: i64| y.clamp(0, image.height() as i64) as u32;
+    let (x0, x1) = (x0.min(x1), x0.max(x1));
+    let (y0, y1) = (y0.min(y1), y0.max(y1));
+
+    (clamp_x(x0), clamp_y(y0), clamp_x(x1), clamp_y(y1))
+}
+
+fn fill_rect(image: &mut RgbaImage, x0: i64, y0: i64, x1: i64, y1: i64, color: Rgba<u8>) {
+    let (x0, y0, x1, y1) = clamp_rect(image, x0, y0, x1, y1);
+    for py in y0..y1 {
+        for px in x0..x1 {
+            image.put_pixel(px, py, color);
+        }
+    }
+}
+
+/// Mixes a color over a pixel, ignoring pixels outside the image
+fn blend_pixel(image: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>, alpha: f64) {
+    if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+        return;
+    }
+
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    for i in 0..3 {
+        let mixed = pixel.0[i] as f64 * (1.0 - alpha) + color.0[i] as f64 * alpha;
+        pixel.0[i] = mixed.round() as u8;
+    }
+}
+
+fn fill_disc(image: &mut RgbaImage, center: (f64, f64), radius: f64, color: Rgba<u8>) {
+    let reach = radius.ceil() as i64;
+    let (cx, cy) = (center.0.round() as i64, center.1.round() as i64);
+    for dy in -reach..=reach {
+        for dx in -reach..=reach {
+            if ((dx * dx + dy * dy) as f64) <= radius * radius + 0.25 {
+                blend_pixel(image, cx + dx, cy + dy, color, 1.0);
+            }
+        }
+    }
+}
+
+fn draw_line(
+    image: &mut RgbaImage,
+    from: (f64, f64),
+    to: (f64, f64),
+    thickness: f64,
+    color: Rgba<u8>,
+) {
+    // Stamp discs along the line, close enough to leave no gaps
+    let length = (to.0 - from.0).hypot(to.1 - from.1);
+    let steps = (length * 2.0).ceil().max(1.0) as usize;
+    for step in 0..=steps {
+        let t = step as f64 / steps as f64;
+        let point = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+        fill_disc(image, point, thickness / 2.0, color);
+    }
+}
+
+fn draw_arrow(
+    image: &mut RgbaImage,
+    from: (f64, f64),
+    to: (f64, f64),
+    thickness: f64,
+    color: Rgba<u8>,
+) {
+    let length = (to.0 - from.0).hypot(to.1 - from.1);
+    if length < 1.0 {
+        return;
+    }
+
+    // Arrowhead as a filled triangle, the shaft stops at its base
+    let head = (thickness * 4.0).max(10.0).min(length);
+    let (ux, uy) = ((to.0 - from.0) / length, (to.1 - from.1) / length);
+    let base = (to.0 - ux * head, to.1 - uy * head);
+    let half_width = head * 0.5;
+    let left = (base.0 - uy * half_width, base.1 + ux * half_width);
+    let right = (base.0 + uy * half_width, base.1 - ux * half_width);
+
+    draw_line(image, from, base, thickness, color);
+    fill_triangle(image, [to, left, right], color);
+}
+
+fn fill_triangle(image: &mut RgbaImage, points: [(f64, f64); 3], color: Rgba<u8>) {
+    let min_x = points.iter().map(|p| p.0).fold(f64::MAX, f64::min).floor() as i64;
+    let max_x = points.iter().map(|p| p.0).fold(f64::MIN, f64::max).ceil() as i64;
+    let min_y = points.iter().map(|p| p.1).fold(f64::MAX, f64::min).floor() as i64;
+    let max_y = points.iter().map(|p| p.1).fold(f64::MIN, f64::max).ceil() as i64;
+
+    let edge = |a: (f64, f64), b: (f64, f64), p: (f64, f64)| {
+        (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+    };
+
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            let p = (x as f64 + 0.5, y as f64 + 0.5);
+            let d0 = edge(points[0], points[1], p);
+            let d1 = edge(points[1], points[2], p);
+            let d2 = edge(points[2], points[0], p);
+            let inside =
+                (d0 >= 0.0 && d1 >= 0.0 && d2 >= 0.0) || (d0 <= 0.0 && d1 <= 0.0 && d2 <= 0.0);
+            if inside {
+                blend_pixel(image, x, y, color, 1.0);
+            }
+        }
+    }
+}
+
+fn draw_text(image: &mut RgbaImage, origin: (f64, f64), text: &str, size: f64, color: Rgba<u8>) {
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.iter().all(|line| line.is_empty()) {
+        return;
+    }
+
+    let Some(font) = label_font() else {
+        return;
+    };
+    let scaled = font.as_scaled(PxScale::from(size.max(4.0) as f32));
+    let ascent = scaled.ascent();
+    let line_height = scaled.height();
+
+    // Lay out every glyph first, the backing box has to be drawn below them
+    let mut glyphs = Vec::new();
+    let mut width: f32 = 0.0;
+    for (row, line) in lines.iter().enumerate() {
+        let baseline = origin.1 as f32 + ascent + line_height * row as f32;
+        let mut x = origin.0 as f32;
+        for c in line.chars() {
+            let mut glyph = scaled.scaled_glyph(c);
+            glyph.position = point(x, baseline);
+            x += scaled.h_advance(glyph.id);
+            glyphs.push(glyph);
+        }
+        width = width.max(x - origin.0 as f32);
+    }
+
+    // Light backing box keeps the label readable on any content
+    let padding = (size / 4.5).round() as i64;
+    let (x0, y0) = (origin.0.round() as i64, origin.1.round() as i64);
+    let x1 = (origin.0 + width as f64).round() as i64;
+    let y1 = (origin.1 + (line_height * lines.len() as f32) as f64).round() as i64;
+    for py in y0 - padding..y1 + padding {
+        for px in x0 - padding..x1 + padding {
+            blend_pixel(image, px, py, Rgba([255, 255, 255, 255]), 0.8);
+        }
+    }
+
+    for glyph in glyphs {
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        let (left, top) = (bounds.min.x as i64, bounds.min.y as i64);
+        outline.draw(|x, y, coverage| {
+            blend_pixel(
+                image,
+                left + x as i64,
+                top + y as i64,
+                color,
+                coverage.min(1.0) as f64,
+            )
+        });
+    }
+}
+
+/// Bold monospace like the selector's preview, covering Latin, Greek and Cyrillic.
+/// Other characters are drawn as boxes.
+const LABEL_FONT: &[u8] = include_bytes!("../fonts/DejaVuSansMono-Bold.ttf");
+
+/// The parsed label font, `None` if it could not be loaded
+fn label_font() -> Option<&'static FontRef<'static>> {
+    static FONT: OnceLock<Option<FontRef<'static>>> = OnceLock::new();
+
+    FONT.get_or_init(|| match FontRef::try_from_slice(LABEL_FONT) {
+        Ok(font) => Some(font),
+        Err(e) => {
+            eprintln!(
+                "Warning: Failed to load the label font, labels are skipped: {}",
+                e
+            );
+            None
+        }
+    })
+    .as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    fn white(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, WHITE)
+    }
+
+    fn area(x: f64, y: f64, width: f64, height: f64) -> (f64, f64, f64, f64) {
+        (x, y, width, height)
+    }
+
+    fn redact((x, y, width, height): (f64, f64, f64, f64)) -> Annotation {
+        Annotation::Redact {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn blur((x, y, width, height): (f64, f64, f64, f64)) -> Annotation {
+        Annotation::Blur {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn text(text: &str) -> Annotation {
+        Annotation::Text {
+            x: 4.0,
+            y: 4.0,
+            text: text.to_string(),
+            color: "#000".to_string(),
+            size: 24.0,
+        }
+    }
+
+    #[test]
+    fn draws_rect_outlines_only() {
+        let mut image = white(20, 20);
+        let rect = Annotation::Rect {
+            x: 1.0,
+            y: 1.0,
+            width: 5.0,
+            height: 4.0,
+            color: "#0000ff".to_string(),
+            thickness: 1.0,
+        };
+        apply(&mut image, &[rect], 2.0);
+
+        // Scaled to 2..12 by 2..10 with 2 pixel thick edges
+        let corners = [(2, 2), (11, 2), (2, 9), (11, 9)];
+        let sides = [(3, 6), (10, 6), (6, 3), (6, 8)];
+        for (x, y) in corners.into_iter().chain(sides) {
+            assert_eq!(*image.get_pixel(x, y), BLUE, "edge pixel {}, {}", x, y);
+        }
+        for (x, y) in [(4, 4), (9, 7), (1, 1), (12, 10), (6, 10)] {
+            assert_eq!(*image.get_pixel(x, y), WHITE, "pixel {}, {}", x, y);
+        }
+    }
+
+    #[test]
+    fn fills_redacted_areas() {
+        let mut image = white(10, 10);
+        apply(&mut image, &[redact(area(2.0, 3.0, 4.0, 2.0))], 1.0);
+
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let inside = (2..6).contains(&x) && (3..5).contains(&y);
+            let expected = if inside { BLACK } else { WHITE };
+            assert_eq!(*pixel, expected, "pixel {}, {}", x, y);
+        }
+    }
+
+    #[test]
+    fn blurs_to_the_block_average() {
+        // Checkerboard of black and white pixels
+        let mut image =
+            RgbaImage::from_fn(12, 12, |x, y| if (x + y) % 2 == 0 { BLACK } else { WHITE });
+        apply(&mut image, &[blur(area(0.0, 0.0, 4.0, 4.0))], 1.0);
+
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_eq!(*image.get_pixel(x, y), Rgba([127, 127, 127, 255]));
+            }
+        }
+        // The checkerboard around the area is untouched
+        assert_eq!(*image.get_pixel(4, 0), BLACK);
+        assert_eq!(*image.get_pixel(5, 0), WHITE);
+        assert_eq!(*image.get_pixel(0, 5), WHITE);
+    }
+
+    #[test]
+    fn clamps_areas_to_the_image() {
+        let mut image = white(8, 8);
+        apply(
+            &mut image,
+            &[
+                redact(area(-5.0, -5.0, 8.0, 8.0)),
+                redact(area(6.0, 6.0, 10.0, 10.0)),
+                redact(area(50.0, 50.0, 4.0, 4.0)),
+                // Averages the 3x3 redacted corner with the rest of its 4x4 area
+                blur(area(-20.0, -20.0, 24.0, 24.0)),
+            ],
+            1.0,
+        );
+
+        assert_eq!(*image.get_pixel(0, 0), Rgba([111, 111, 111, 255]));
+        assert_eq!(*image.get_pixel(3, 3), Rgba([111, 111, 111, 255]));
+        assert_eq!(*image.get_pixel(4, 4), WHITE);
+        assert_eq!(*image.get_pixel(5, 5), WHITE);
+        assert_eq!(*image.get_pixel(6, 6), BLACK);
+        assert_eq!(*image.get_pixel(7, 7), BLACK);
+    }
+
+    #[test]
+    fn renders_non_ascii_text() {
+        let render = |label: &str| {
+            let mut image = white(64, 40);
+            apply(&mut image, &[text(label)], 1.0);
+            image
+        };
+
+        let accented = render("é");
+        assert!(accented.pixels().any(|pixel| pixel.0[0] < 64));
+        assert_ne!(accented, render("?"));
+        assert_ne!(accented, render("e"));
+        assert_ne!(render("Ж"), render("?"));
+    }
+}
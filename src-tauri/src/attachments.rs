@@ -0,0 +1,272 @@
+use crate::config::ScreenshotConfig;
+use crate::redaction::Redactor;
+use crate::screenshot;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Largest file that will be read as an attachment
+pub const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Extracted text is truncated beyond this many characters
+pub const MAX_TEXT_CHARS: usize = 200_000;
+
+/// Largest combined size of the documents and images sent with one request
+pub const MAX_TOTAL_SIZE: u64 = 20 * 1024 * 1024;
+
+/// Largest estimated token count of the documents and images sent with one request
+pub const MAX_TOTAL_TOKENS: usize = 100_000;
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "webp", "tiff", "tif"];
+
+/// A document attached to a question, already converted to text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    pub path: String,
+    /// "text" or "pdf"
+    pub kind: String,
+    pub content: String,
+    pub size: u64,
+    pub truncated: bool,
+    /// Rough token estimate of `content`
+    pub tokens: usize,
+}
+
+/// A file read from disk, either a document or an image data URL
+pub enum LoadedFile {
+    Document(Attachment),
+    Image(String),
+}
+
+/// Reads a file as an attachment: images become data URLs encoded like screenshots,
+/// PDFs are text-extracted and anything else must be UTF-8 text
+pub fn load_file(
+    path: &Path,
+    image_options: &ScreenshotConfig,
+    redactor: Option<&Redactor>,
+) -> Result<LoadedFile, String> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string());
+
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    if !metadata.is_file() {
+        return Err(format!("{} is not a file", name));
+    }
+    if metadata.len() > MAX_FILE_SIZE {
+        return Err(format!(
+            "{} is too large ({} MB, limit is {} MB)",
+            name,
+            metadata.len() / (1024 * 1024),
+            MAX_FILE_SIZE / (1024 * 1024)
+        ));
+    }
+
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        let image =
+            image::open(path).map_err(|e| format!("Failed to decode image {}: {}", name, e))?;
+        return screenshot::encode_image(&image.to_rgba8(), image_options, redactor)
+            .map(LoadedFile::Image);
+    }
+
+    let (kind, content) = if extension == "pdf" {
+        let text = pdf_extract::extract_text(path)
+            .map_err(|e| format!("Failed to extract text from {}: {}", name, e))?;
+        ("pdf", text)
+    } else {
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        let text = String::from_utf8(bytes).map_err(|_| format!("{} is not a text file", name))?;
+        ("text", text)
+    };
+
+    let (content, truncated) = truncate_text(content, MAX_TEXT_CHARS);
+    let tokens = estimate_tokens(&content);
+
+    Ok(LoadedFile::Document(Attachment {
+        name,
+        path: path.to_string_lossy().to_string(),
+        kind: kind.to_string(),
+        content,
+        size: metadata.len(),
+        truncated,
+        tokens,
+    }))
+}
+
+/// Estimates the token count of text, assuming about four characters per token
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Checks that a set of documents and image data URLs fits in a single request
+pub fn check_limits(attachments: &[Attachment], images: &[String]) -> Result<(), String> {
+    let images = images
+        .iter()
+        .map(|image| screenshot::describe_data_url(image))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let size = attachments.iter().map(|a| a.size).sum::<u64>()
+        + images.iter().map(|i| i.bytes as u64).sum::<u64>();
+    if size > MAX_TOTAL_SIZE {
+        return Err(format!(
+            "Attached files and images are too large ({} MB, limit is {} MB)",
+            size / (1024 * 1024),
+            MAX_TOTAL_SIZE / (1024 * 1024)
+        ));
+    }
+
+    let tokens = attachments.iter().map(|a| a.tokens).sum::<usize>()
+        + images.iter().map(|i| i.tokens as usize).sum::<usize>();
+    if tokens > MAX_TOTAL_TOKENS {
+        return Err(format!(
+            "Attached files and images are too long (about {} tokens, limit is {})",
+            tokens, MAX_TOTAL_TOKENS
+        ));
+    }
+
+    Ok(())
+}
+
+/// Parses `text/uri-list` content into local file paths
+pub fn parse_uri_list(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.strip_prefix("file://"))
+        .filter_map(|uri| {
+            // Files on other hosts of file://host/path aren't reachable as local paths
+            let (host, path) = uri.split_at(uri.find('/')?);
+            (host.is_empty() || host.eq_ignore_ascii_case("localhost"))
+                .then(|| PathBuf::from(percent_decode(path)))
+        })
+        .collect()
+}
+
+/// Formats documents as prompt text, one fenced block per file
+pub fn format_for_prompt(attachments: &[Attachment]) -> String {
+    attachments
+        .iter()
+        .map(|a| {
+            let note = if a.truncated { " (truncated)" } else { "" };
+            format!("File: {}{}\n```\n{}\n```", a.name, note, a.content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn truncate_text(text: String, max_chars: usize) -> (String, bool) {
+    match text.char_indices().nth(max_chars) {
+        Some((index, _)) => (text[..index].to_string(), true),
+        None => (text, false),
+    }
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            // from_str_radix alone would accept a sign such as "%+1"
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+            if let Some(byte) = hex {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_file_uris_and_skips_comments() {
+        let list = "# copied from a file manager\r\n\
+                    file:///home/jane/notes.txt\r\n\
+                    \r\n\
+                    https://example.com/page.html\r\n\
+                    file://localhost/srv/report.pdf\r\n";
+
+        assert_eq!(
+            parse_uri_list(list),
+            vec![
+                PathBuf::from("/home/jane/notes.txt"),
+                PathBuf::from("/srv/report.pdf"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_files_on_other_hosts() {
+        let list = "file://laptop/srv/report.pdf\n\
+                    file://LOCALHOST/tmp/a.txt\n\
+                    file://nohost";
+
+        assert_eq!(parse_uri_list(list), vec![PathBuf::from("/tmp/a.txt")]);
+    }
+
+    #[test]
+    fn decodes_escaped_paths() {
+        assert_eq!(
+            parse_uri_list("file:///tmp/My%20Files/caf%C3%A9%20%E2%9C%93.md"),
+            vec![PathBuf::from("/tmp/My Files/café ✓.md")]
+        );
+    }
+
+    #[test]
+    fn decodes_multi_byte_escapes() {
+        assert_eq!(percent_decode("%E6%97%A5%E6%9C%AC"), "日本");
+        assert_eq!(percent_decode("%F0%9F%93%84.txt"), "📄.txt");
+    }
+
+    #[test]
+    fn keeps_invalid_escapes_literally() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("50%2"), "50%2");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(percent_decode("%+1"), "%+1");
+        assert_eq!(percent_decode("%%41"), "%A");
+    }
+
+    #[test]
+    fn replaces_invalid_utf8() {
+        assert_eq!(percent_decode("a%FFb"), "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn truncates_at_a_character_boundary() {
+        assert_eq!(
+            truncate_text("héllo wörld".to_string(), 7),
+            ("héllo w".to_string(), true)
+        );
+        assert_eq!(
+            truncate_text("日本語テキスト".to_string(), 3),
+            ("日本語".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn keeps_text_within_the_limit() {
+        assert_eq!(
+            truncate_text("日本語".to_string(), 3),
+            ("日本語".to_string(), false)
+        );
+        assert_eq!(truncate_text(String::new(), 0), (String::new(), false));
+    }
+}
@@ -1,10 +1,593 @@
-use anyhow::Result;
+use crate::active_window::{self, ActiveApp};
+use crate::attachments;
+use crate::config::QuestionTemplate;
+use anyhow::{anyhow, Result};
+use enigo::Direction::{Click, Press, Release};
+use enigo::{Enigo, Key, Keyboard, Settings};
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+/// How long to wait for the clipboard to change after simulating a copy
+const COPY_TIMEOUT_MS: u64 = 500;
+const COPY_POLL_INTERVAL_MS: u64 = 20;
+
+/// Which strategy produced the captured text
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMethod {
+    Primary,
+    Selection,
+    ClipboardCopy,
+    RichClipboardCopy,
+    Clipboard,
+    RichClipboard,
+    FileList,
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct CapturedSelection {
+    pub text: String,
+    pub method: CaptureMethod,
+    /// Files selected in a file manager instead of text
+    pub files: Vec<PathBuf>,
+}
+
+impl CapturedSelection {
+    fn new(text: String, method: CaptureMethod) -> Self {
+        Self {
+            text,
+            method,
+            files: Vec::new(),
+        }
+    }
+}
+
+/// Content placed on the clipboard by a simulated copy
+#[derive(Default)]
+struct CopiedContent {
+    text: String,
+    files: Vec<PathBuf>,
+}
+
+/// Clipboard formats arboard can read, saved before a simulated copy.
+/// arboard replaces the whole clipboard on every write, so `restore` can only put one back.
+#[derive(Default)]
+struct ClipboardSnapshot {
+    text: Option<String>,
+    html: Option<String>,
+    image: Option<arboard::ImageData<'static>>,
+    files: Vec<PathBuf>,
+}
+
+impl ClipboardSnapshot {
+    fn read() -> Self {
+        let Ok(mut clipboard) = arboard::Clipboard::new() else {
+            return Self::default();
+        };
+
+        Self {
+            text: clipboard.get().text().ok(),
+            html: clipboard.get().html().ok(),
+            image: clipboard.get().image().ok(),
+            files: read_clipboard_files(),
+        }
+    }
+
+    /// Puts back the richest saved format: files, then the image, then HTML together with its
+    /// plain text, then the text. Other formats copied alongside it are lost.
+    fn restore(self) -> Result<()> {
+        let mut clipboard = arboard::Clipboard::new()?;
+
+        if !self.files.is_empty() {
+            clipboard.set().file_list(&self.files)?;
+        } else if let Some(image) = self.image {
+            clipboard.set().image(image)?;
+        } else if let Some(html) = self.html {
+            clipboard.set().html(html, self.text)?;
+        } else if let Some(text) = self.text {
+            clipboard.set().text(text)?;
+        } else {
+            clipboard.clear()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Which copy shortcut the focused application expects
+#[derive(Debug, Clone, Copy)]
+enum CopyShortcut {
+    /// Ctrl+C, or Cmd+C on macOS
+    Standard,
+    /// Ctrl+Shift+C, since Ctrl+C interrupts the program running in a terminal
+    Terminal,
+}
 
 /// Captures currently selected text using the selection crate
-/// This uses UI Automation API on Windows with clipboard as fallback
-pub async fn capture_selected_text() -> Result<String> {
-    // Run in blocking task since selection::get_text() is synchronous
-    let text = tokio::task::spawn_blocking(|| selection::get_text()).await?;
+/// This uses UI Automation API on Windows with clipboard as fallback,
+/// then simulates a copy shortcut if nothing was returned.
+/// On Linux the copy comes first since PRIMARY keeps old text after the selection
+/// is gone, so PRIMARY is only read when nothing was copied.
+pub async fn capture_selected_text(active_app: &ActiveApp) -> Result<CapturedSelection> {
+    // The selection crate reads PRIMARY on Linux, see `linux_selection`
+    #[cfg(not(target_os = "linux"))]
+    {
+        // Run in blocking task since selection::get_text() is synchronous
+        let text = tokio::task::spawn_blocking(|| selection::get_text()).await?;
+
+        if !text.trim().is_empty() {
+            return Ok(record(CapturedSelection::new(
+                text,
+                CaptureMethod::Selection,
+            )));
+        }
+    }
+
+    // Electron apps and terminals often hide their selection, so copy it instead
+    let copied = match copy_selection_via_clipboard(active_app, false).await {
+        Ok(copied) => copied,
+        Err(e) => {
+            eprintln!("Warning: Clipboard copy fallback failed: {}", e);
+            CopiedContent::default()
+        }
+    };
+
+    // Files selected in a file manager are passed on as attachments
+    if !copied.files.is_empty() {
+        return Ok(record(CapturedSelection {
+            text: String::new(),
+            method: CaptureMethod::FileList,
+            files: copied.files,
+        }));
+    }
+
+    #[cfg(target_os = "linux")]
+    if copied.text.trim().is_empty() {
+        return Ok(record(linux_selection().await?));
+    }
+
+    let text = copied.text;
+    let method = if text.is_empty() {
+        CaptureMethod::None
+    } else {
+        CaptureMethod::ClipboardCopy
+    };
+
+    Ok(record(CapturedSelection::new(text, method)))
+}
+
+/// Reads the PRIMARY selection, falling back to the selection crate when it can't be read
+#[cfg(target_os = "linux")]
+async fn linux_selection() -> Result<CapturedSelection> {
+    match tokio::task::spawn_blocking(primary::read_primary_selection).await? {
+        Ok(text) if !text.trim().is_empty() => {
+            return Ok(CapturedSelection::new(text, CaptureMethod::Primary));
+        }
+        Ok(_) => return Ok(CapturedSelection::new(String::new(), CaptureMethod::None)),
+        Err(e) => eprintln!("Warning: Failed to read PRIMARY selection: {}", e),
+    }
+
+    let text = tokio::task::spawn_blocking(selection::get_text).await?;
+    let method = if text.trim().is_empty() {
+        CaptureMethod::None
+    } else {
+        CaptureMethod::Selection
+    };
+
+    Ok(CapturedSelection::new(text, method))
+}
+
+/// Captures the selection with its formatting by copying it and converting
+/// the clipboard HTML to Markdown, falling back to plain text capture
+pub async fn capture_rich_selection(active_app: &ActiveApp) -> Result<CapturedSelection> {
+    match copy_selection_via_clipboard(active_app, true).await {
+        Ok(copied) if !copied.text.trim().is_empty() => {
+            return Ok(record(CapturedSelection::new(
+                copied.text,
+                CaptureMethod::RichClipboardCopy,
+            )));
+        }
+        Ok(copied) if !copied.files.is_empty() => {
+            return Ok(record(CapturedSelection {
+                text: String::new(),
+                method: CaptureMethod::FileList,
+                files: copied.files,
+            }));
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Warning: Rich selection copy failed: {}", e),
+    }
+
+    capture_selected_text(active_app).await
+}
+
+/// Captures a template's input according to its input source policy:
+/// "selection", "selection_or_clipboard", "clipboard" or "ask",
+/// and its input format: "plain" or "rich"
+pub async fn capture_template_input(
+    app: &AppHandle,
+    active_app: &ActiveApp,
+    template: &QuestionTemplate,
+) -> Result<CapturedSelection> {
+    let rich = template.input_format == "rich";
+    let input_source = template.input_source.as_str();
+
+    if input_source != "clipboard" {
+        let captured = if rich {
+            capture_rich_selection(active_app).await?
+        } else {
+            capture_selected_text(active_app).await?
+        };
+        let has_input = !captured.text.is_empty() || !captured.files.is_empty();
+        if has_input || input_source != "selection_or_clipboard" {
+            return Ok(captured);
+        }
+    }
+
+    // Use the current clipboard content as input
+    if rich {
+        if let Some(markdown) = tokio::task::spawn_blocking(read_clipboard_markdown).await? {
+            return Ok(record(CapturedSelection::new(
+                markdown,
+                CaptureMethod::RichClipboard,
+            )));
+        }
+    }
+
+    let text = app.clipboard().read_text().unwrap_or_default();
+    let method = if text.is_empty() {
+        CaptureMethod::None
+    } else {
+        CaptureMethod::Clipboard
+    };
+
+    Ok(record(CapturedSelection::new(text, method)))
+}
+
+/// Reads an image from the clipboard through the clipboard plugin
+pub fn read_clipboard_image(app: &AppHandle) -> Result<image::RgbaImage> {
+    let image = app
+        .clipboard()
+        .read_image()
+        .map_err(|_| anyhow!("The clipboard does not contain an image"))?;
+
+    image::RgbaImage::from_raw(image.width(), image.height(), image.rgba().to_vec())
+        .ok_or_else(|| anyhow!("The clipboard image has an unexpected size"))
+}
+
+/// Reads HTML from the clipboard and converts it to Markdown
+fn read_clipboard_markdown() -> Option<String> {
+    // The clipboard plugin only exposes plain text, so HTML is read through arboard
+    let html = arboard::Clipboard::new().ok()?.get().html().ok()?;
+    let markdown = html2md::parse_html(&html);
+    let markdown = markdown.trim();
+
+    if markdown.is_empty() {
+        None
+    } else {
+        Some(markdown.to_string())
+    }
+}
+
+/// Reads a file list from the clipboard, as copied by file managers
+fn read_clipboard_files() -> Vec<PathBuf> {
+    let files = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get().file_list())
+        .unwrap_or_default();
+
+    // arboard only sees the XWayland clipboard, ask the compositor directly
+    #[cfg(target_os = "linux")]
+    if files.is_empty() && std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        return read_wayland_uri_list();
+    }
+
+    files
+}
+
+/// Reads the Wayland clipboard's text/uri-list offer, empty when it has none
+#[cfg(target_os = "linux")]
+fn read_wayland_uri_list() -> Vec<PathBuf> {
+    use std::io::Read;
+    use wl_clipboard_rs::paste::{get_contents, ClipboardType, MimeType, Seat};
+
+    let Ok((mut pipe, _mime_type)) = get_contents(
+        ClipboardType::Regular,
+        Seat::Unspecified,
+        MimeType::Specific("text/uri-list"),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut contents = Vec::new();
+    match pipe.read_to_end(&mut contents) {
+        Ok(_) => attachments::parse_uri_list(&String::from_utf8_lossy(&contents)),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn record(captured: CapturedSelection) -> CapturedSelection {
+    println!(
+        "Captured {} chars and {} files of selected text via {:?}",
+        captured.text.chars().count(),
+        captured.files.len(),
+        captured.method
+    );
+    log::info!("Selection capture method: {:?}", captured.method);
+    captured
+}
+
+/// Simulates a copy shortcut and reads the selection from the clipboard.
+/// The clipboard is never cleared: a copy is detected as a change, and only then
+/// is the previous content put back afterwards.
+/// With `rich`, copied HTML is returned as Markdown when available.
+async fn copy_selection_via_clipboard(active_app: &ActiveApp, rich: bool) -> Result<CopiedContent> {
+    let snapshot = tokio::task::spawn_blocking(ClipboardSnapshot::read).await?;
+    let change_count = clipboard_change_count();
+
+    let shortcut = if active_window::is_terminal(active_app) {
+        CopyShortcut::Terminal
+    } else {
+        CopyShortcut::Standard
+    };
+    tokio::task::spawn_blocking(move || simulate_copy(shortcut)).await??;
+
+    // Wait for the target application to fill the clipboard
+    let mut changed = false;
+    let mut waited = 0;
+    while waited < COPY_TIMEOUT_MS {
+        tokio::time::sleep(tokio::time::Duration::from_millis(COPY_POLL_INTERVAL_MS)).await;
+        waited += COPY_POLL_INTERVAL_MS;
+
+        changed = match (change_count, clipboard_change_count()) {
+            (Some(before), Some(now)) => before != now,
+            // Without a change counter, compare the content. Copying the exact
+            // text that is already on the clipboard goes unnoticed.
+            _ => {
+                let (text, files) = tokio::task::spawn_blocking(read_text_and_files).await?;
+                text != snapshot.text || files != snapshot.files
+            }
+        };
+        if changed {
+            break;
+        }
+    }
+
+    // Nothing was copied, and the clipboard was left untouched
+    if !changed {
+        return Ok(CopiedContent::default());
+    }
+
+    let copied = tokio::task::spawn_blocking(move || read_copied_content(rich)).await?;
+
+    // Restore original clipboard
+    if let Err(e) = tokio::task::spawn_blocking(move || snapshot.restore()).await? {
+        eprintln!("Warning: Failed to restore the clipboard: {}", e);
+    }
+
+    Ok(copied)
+}
+
+/// Reads what a simulated copy placed on the clipboard
+fn read_copied_content(rich: bool) -> CopiedContent {
+    let (text, files) = read_text_and_files();
+    if !files.is_empty() {
+        return CopiedContent {
+            text: String::new(),
+            files,
+        };
+    }
+
+    let text = text.unwrap_or_default();
+    let text = match rich && !text.is_empty() {
+        true => read_clipboard_markdown().unwrap_or(text),
+        false => text,
+    };
+
+    CopiedContent {
+        text,
+        files: Vec::new(),
+    }
+}
+
+fn read_text_and_files() -> (Option<String>, Vec<PathBuf>) {
+    let text = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get().text());
+    (text.ok(), read_clipboard_files())
+}
+
+/// The platform's clipboard change counter, which every copy increments
+fn clipboard_change_count() -> Option<i64> {
+    #[cfg(target_os = "windows")]
+    {
+        clipboard_win::raw::seq_num().map(|count| i64::from(count.get()))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Some(objc2_app_kit::NSPasteboard::generalPasteboard().changeCount() as i64)
+    }
+
+    // X11 and Wayland have no counter
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        None
+    }
+}
+
+fn simulate_copy(shortcut: CopyShortcut) -> Result<()> {
+    let mut enigo = Enigo::new(&Settings::default())
+        .map_err(|e| anyhow!("Failed to initialize enigo: {:?}", e))?;
+
+    // Release hotkey modifiers that are still held so they don't alter the shortcut
+    for modifier in [Key::Alt, Key::Shift, Key::Control, Key::Meta] {
+        let _ = enigo.key(modifier, Release);
+    }
+
+    #[cfg(target_os = "macos")]
+    let copy_modifier = Key::Meta;
+    #[cfg(not(target_os = "macos"))]
+    let copy_modifier = Key::Control;
+
+    // Terminals on macOS copy with Cmd+C like everything else
+    let with_shift = matches!(shortcut, CopyShortcut::Terminal) && !cfg!(target_os = "macos");
+
+    enigo
+        .key(copy_modifier, Press)
+        .map_err(|e| anyhow!("Failed to press copy modifier: {:?}", e))?;
+    if with_shift {
+        enigo
+            .key(Key::Shift, Press)
+            .map_err(|e| anyhow!("Failed to press Shift: {:?}", e))?;
+    }
+    let clicked = enigo
+        .key(Key::Unicode('c'), Click)
+        .map_err(|e| anyhow!("Failed to press C: {:?}", e));
+    if with_shift {
+        let _ = enigo.key(Key::Shift, Release);
+    }
+    enigo
+        .key(copy_modifier, Release)
+        .map_err(|e| anyhow!("Failed to release copy modifier: {:?}", e))?;
+
+    clicked
+}
+
+/// Linux PRIMARY selection backend (X11 and wlr/ext data control on Wayland)
+#[cfg(target_os = "linux")]
+mod primary {
+    use anyhow::{anyhow, Result};
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, CreateWindowAux, WindowClass};
+    use x11rb::protocol::Event;
+
+    /// How long the selection owner gets to answer a conversion request
+    const SELECTION_TIMEOUT_MS: u64 = 300;
+
+    /// Reads the PRIMARY selection, preferring Wayland when a compositor is running
+    pub fn read_primary_selection() -> Result<String> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            match read_wayland_primary() {
+                Ok(text) => return Ok(text),
+                // Compositors without data control fall through to XWayland
+                Err(e) => eprintln!("Warning: Wayland PRIMARY selection unavailable: {}", e),
+            }
+        }
+
+        read_x11_primary()
+    }
+
+    fn read_wayland_primary() -> Result<String> {
+        use wl_clipboard_rs::paste::{get_contents, ClipboardType, Error, MimeType, Seat};
+
+        match get_contents(ClipboardType::Primary, Seat::Unspecified, MimeType::Text) {
+            Ok((mut pipe, _mime_type)) => {
+                let mut contents = Vec::new();
+                pipe.read_to_end(&mut contents)?;
+                Ok(String::from_utf8_lossy(&contents).to_string())
+            }
+            Err(Error::NoSeats) | Err(Error::ClipboardEmpty) | Err(Error::NoMimeType) => {
+                Ok(String::new())
+            }
+            Err(e) => Err(anyhow!(e)),
+        }
+    }
+
+    fn read_x11_primary() -> Result<String> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let screen = &conn.setup().roots[screen_num];
+
+        // Nobody owns the selection, so nothing is highlighted
+        let owner = conn
+            .get_selection_owner(AtomEnum::PRIMARY.into())?
+            .reply()?
+            .owner;
+        if owner == x11rb::NONE {
+            return Ok(String::new());
+        }
+
+        // Hidden window that receives the converted selection
+        let window = conn.generate_id()?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            screen.root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &CreateWindowAux::new(),
+        )?;
+
+        let utf8_string = conn.intern_atom(false, b"UTF8_STRING")?.reply()?.atom;
+        let incr = conn.intern_atom(false, b"INCR")?.reply()?.atom;
+        let property = conn
+            .intern_atom(false, b"ASK_ANYWHERE_SELECTION")?
+            .reply()?
+            .atom;
+
+        conn.convert_selection(
+            window,
+            AtomEnum::PRIMARY.into(),
+            utf8_string,
+            property,
+            x11rb::CURRENT_TIME,
+        )?;
+        conn.flush()?;
+
+        // Wait for the owner to answer
+        let deadline = Instant::now() + Duration::from_millis(SELECTION_TIMEOUT_MS);
+        let result = loop {
+            match conn.poll_for_event()? {
+                Some(Event::SelectionNotify(event)) if event.requestor == window => {
+                    if event.property == x11rb::NONE {
+                        break Ok(String::new());
+                    }
+
+                    let reply = conn
+                        .get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX / 4)?
+                        .reply()?;
+
+                    // Large selections are sent incrementally, leave them to the fallback
+                    if reply.type_ == incr {
+                        break Err(anyhow!("PRIMARY selection too large for a single transfer"));
+                    }
+
+                    break Ok(String::from_utf8_lossy(&reply.value).to_string());
+                }
+                Some(_) => {}
+                None => {
+                    if Instant::now() >= deadline {
+                        break Err(anyhow!("Timed out waiting for the PRIMARY selection"));
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            }
+        };
+
+        let _ = conn.destroy_window(window);
+        let _ = conn.flush();
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_capture_methods_in_snake_case() {
+        let method = |method| serde_json::to_value(method).unwrap();
 
-    Ok(text)
+        assert_eq!(method(CaptureMethod::Selection), "selection");
+        assert_eq!(method(CaptureMethod::ClipboardCopy), "clipboard_copy");
+        assert_eq!(method(CaptureMethod::None), "none");
+    }
 }
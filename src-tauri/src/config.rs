@@ -12,6 +12,46 @@ pub struct AppConfig {
     pub popup_width: f64,
     #[serde(default = "default_max_popup_height")]
     pub max_popup_height: f64,
+    #[serde(default)]
+    pub hotkeys_paused: bool,
+    #[serde(default)]
+    pub include_window_context: bool,
+    #[serde(default)]
+    pub screenshot: ScreenshotConfig,
+    #[serde(default)]
+    pub ocr: OcrConfig,
+    /// What to do with screenshots when the selected model has no vision:
+    /// "block", "route" to `vision_model`, or "ocr" (blocks while the OCR models are missing)
+    #[serde(default = "default_vision_fallback")]
+    pub vision_fallback: String,
+    /// Name of the model "route" sends screenshots to, empty for the first vision-capable model
+    #[serde(default)]
+    pub vision_model: String,
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    /// Record conversations, including background runs, in the history database
+    #[serde(default)]
+    pub save_history: bool,
+    /// Number of conversations to keep in the history (0 keeps everything)
+    #[serde(default = "default_history_retention_count")]
+    pub history_retention_count: usize,
+}
+
+impl AppConfig {
+    /// Parses a config saved in the store, including ones written by older versions
+    pub fn from_stored(value: serde_json::Value) -> serde_json::Result<Self> {
+        let mut config: AppConfig = serde_json::from_value(value)?;
+        config.migrate_legacy_fields();
+        Ok(config)
+    }
+
+    /// Maps fields of older configs onto the settings that replaced them
+    pub fn migrate_legacy_fields(&mut self) {
+        // Configs from before `vision_fallback` only had the OCR switch
+        if let Some(enabled) = self.ocr.enabled.take() {
+            self.vision_fallback = if enabled { "ocr" } else { "block" }.to_string();
+        }
+    }
 }
 
 fn default_popup_width() -> f64 {
@@ -22,6 +62,14 @@ fn default_max_popup_height() -> f64 {
     600.0
 }
 
+fn default_history_retention_count() -> usize {
+    500
+}
+
+fn default_vision_fallback() -> String {
+    "ocr".to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelConfig {
     pub name: String,
@@ -43,23 +91,159 @@ pub struct QuestionTemplate {
     pub hotkey: Option<String>,
     #[serde(default)]
     pub background_mode: bool,
+    #[serde(default)]
+    pub hold_mode: bool,
+    #[serde(default)]
+    pub include_apps: Vec<String>,
+    #[serde(default)]
+    pub exclude_apps: Vec<String>,
+    #[serde(default = "default_input_source")]
+    pub input_source: String,
+    #[serde(default = "default_input_format")]
+    pub input_format: String,
 }
 
 fn default_template_action() -> String {
     "none".to_string()
 }
 
+fn default_input_source() -> String {
+    "selection".to_string()
+}
+
+fn default_input_format() -> String {
+    "plain".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScreenshotConfig {
+    /// Longest edge in pixels, larger images are downscaled (0 keeps full resolution)
+    #[serde(default = "default_max_dimension")]
+    pub max_dimension: u32,
+    /// "png", "jpeg" or "webp"
+    #[serde(default = "default_screenshot_format")]
+    pub format: String,
+    /// JPEG quality from 1 to 100
+    #[serde(default = "default_screenshot_quality")]
+    pub quality: u8,
+    /// Keep a copy of every capture in the history folder
+    #[serde(default)]
+    pub save_to_disk: bool,
+    /// History folder, the app data folder is used when empty
+    #[serde(default)]
+    pub save_folder: String,
+    /// Number of saved screenshots to keep (0 keeps everything)
+    #[serde(default = "default_retention_count")]
+    pub retention_count: usize,
+    /// Scrolling captures are split into tiles this tall (0 sends one tall image)
+    #[serde(default = "default_scroll_tile_height")]
+    pub scroll_tile_height: u32,
+}
+
+fn default_max_dimension() -> u32 {
+    2048
+}
+
+fn default_screenshot_format() -> String {
+    "png".to_string()
+}
+
+fn default_screenshot_quality() -> u8 {
+    85
+}
+
+fn default_retention_count() -> usize {
+    200
+}
+
+fn default_scroll_tile_height() -> u32 {
+    1600
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        Self {
+            max_dimension: default_max_dimension(),
+            format: default_screenshot_format(),
+            quality: default_screenshot_quality(),
+            save_to_disk: false,
+            save_folder: String::new(),
+            retention_count: default_retention_count(),
+            scroll_tile_height: default_scroll_tile_height(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionConfig {
+    /// Find sensitive text in screenshots with OCR and pixelate it before sending
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_true")]
+    pub emails: bool,
+    #[serde(default = "default_true")]
+    pub api_keys: bool,
+    #[serde(default = "default_true")]
+    pub card_numbers: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            emails: true,
+            api_keys: true,
+            card_numbers: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OcrConfig {
+    /// OCR switch of configs saved before `vision_fallback`, see `migrate_legacy_fields`
+    #[serde(default, skip_serializing)]
+    pub enabled: Option<bool>,
+    /// Folder with the OCR models, the app data folder is used when empty
+    #[serde(default)]
+    pub model_dir: String,
+    /// Selects a region and copies its text to the clipboard
+    #[serde(default)]
+    pub hotkey: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HotkeyConfig {
     pub popup_hotkey: String,
     #[serde(default = "default_screenshot_hotkey")]
     pub screenshot_hotkey: String,
+    #[serde(default)]
+    pub popup_hold_mode: bool,
+    /// "region" opens the selector, "window" captures the window under the cursor
+    #[serde(default = "default_screenshot_mode")]
+    pub screenshot_mode: String,
+    /// Attaches the clipboard image and opens the popup
+    #[serde(default)]
+    pub clipboard_image_hotkey: Option<String>,
+    /// Selects a region and captures it while scrolling
+    #[serde(default)]
+    pub scroll_capture_hotkey: Option<String>,
+    /// Opens the conversation history search window
+    #[serde(default)]
+    pub history_search_hotkey: Option<String>,
 }
 
 fn default_screenshot_hotkey() -> String {
     "Alt+Shift+S".to_string()
 }
 
+fn default_screenshot_mode() -> String {
+    "region".to_string()
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
@@ -78,6 +262,11 @@ impl Default for AppConfig {
                     action: "replace".to_string(),
                     hotkey: Some("Alt+Shift+Q".to_string()),
                     background_mode: true,
+                    hold_mode: false,
+                    include_apps: Vec::new(),
+                    exclude_apps: Vec::new(),
+                    input_source: "selection".to_string(),
+                    input_format: "plain".to_string(),
                 },
                 QuestionTemplate {
                     id: "translate".to_string(),
@@ -86,6 +275,11 @@ impl Default for AppConfig {
                     action: "none".to_string(),
                     hotkey: Some("Alt+Q".to_string()),
                     background_mode: false,
+                    hold_mode: false,
+                    include_apps: Vec::new(),
+                    exclude_apps: Vec::new(),
+                    input_source: "selection".to_string(),
+                    input_format: "plain".to_string(),
                 },
                 QuestionTemplate {
                     id: "summarize".to_string(),
@@ -94,16 +288,35 @@ impl Default for AppConfig {
                     action: "copy".to_string(),
                     hotkey: None,
                     background_mode: false,
+                    hold_mode: false,
+                    include_apps: Vec::new(),
+                    exclude_apps: Vec::new(),
+                    input_source: "selection".to_string(),
+                    input_format: "plain".to_string(),
                 },
             ],
             hotkeys: HotkeyConfig {
                 popup_hotkey: "Alt+S".to_string(),
                 screenshot_hotkey: "Alt+Shift+S".to_string(),
+                popup_hold_mode: false,
+                screenshot_mode: default_screenshot_mode(),
+                clipboard_image_hotkey: None,
+                scroll_capture_hotkey: None,
+                history_search_hotkey: None,
             },
             selected_model_index: 0,
             autostart: false,
             popup_width: 500.0,
             max_popup_height: 600.0,
+            hotkeys_paused: false,
+            include_window_context: false,
+            screenshot: ScreenshotConfig::default(),
+            ocr: OcrConfig::default(),
+            vision_fallback: default_vision_fallback(),
+            vision_model: String::new(),
+            redaction: RedactionConfig::default(),
+            save_history: false,
+            history_retention_count: default_history_retention_count(),
         }
     }
 }
@@ -0,0 +1,873 @@
+use crate::screenshot_history;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Schema changes, applied in order and tracked with `PRAGMA user_version`
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE conversations (
+        id INTEGER PRIMARY KEY,
+        title TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE exchanges (
+        id INTEGER PRIMARY KEY,
+        conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+        source TEXT NOT NULL,
+        template TEXT,
+        model TEXT NOT NULL,
+        input TEXT NOT NULL,
+        output TEXT NOT NULL,
+        screenshots TEXT NOT NULL DEFAULT '[]',
+        started_at INTEGER NOT NULL,
+        finished_at INTEGER NOT NULL
+    );
+    CREATE INDEX exchanges_conversation ON exchanges(conversation_id);
+",
+    "
+    CREATE VIRTUAL TABLE exchanges_fts USING fts5(
+        input, output, content='exchanges', content_rowid='id'
+    );
+    INSERT INTO exchanges_fts(exchanges_fts) VALUES ('rebuild');
+    CREATE TRIGGER exchanges_fts_insert AFTER INSERT ON exchanges BEGIN
+        INSERT INTO exchanges_fts(rowid, input, output) VALUES (new.id, new.input, new.output);
+    END;
+    CREATE TRIGGER exchanges_fts_delete AFTER DELETE ON exchanges BEGIN
+        INSERT INTO exchanges_fts(exchanges_fts, rowid, input, output)
+        VALUES ('delete', old.id, old.input, old.output);
+    END;
+    CREATE TRIGGER exchanges_fts_update AFTER UPDATE ON exchanges BEGIN
+        INSERT INTO exchanges_fts(exchanges_fts, rowid, input, output)
+        VALUES ('delete', old.id, old.input, old.output);
+        INSERT INTO exchanges_fts(rowid, input, output) VALUES (new.id, new.input, new.output);
+    END;
+",
+    "
+    ALTER TABLE exchanges ADD COLUMN captured_text TEXT NOT NULL DEFAULT '';
+    DROP TRIGGER exchanges_fts_insert;
+    DROP TRIGGER exchanges_fts_delete;
+    DROP TRIGGER exchanges_fts_update;
+    DROP TABLE exchanges_fts;
+    CREATE VIRTUAL TABLE exchanges_fts USING fts5(
+        input, output, captured_text, content='exchanges', content_rowid='id'
+    );
+    INSERT INTO exchanges_fts(exchanges_fts) VALUES ('rebuild');
+    CREATE TRIGGER exchanges_fts_insert AFTER INSERT ON exchanges BEGIN
+        INSERT INTO exchanges_fts(rowid, input, output, captured_text)
+        VALUES (new.id, new.input, new.output, new.captured_text);
+    END;
+    CREATE TRIGGER exchanges_fts_delete AFTER DELETE ON exchanges BEGIN
+        INSERT INTO exchanges_fts(exchanges_fts, rowid, input, output, captured_text)
+        VALUES ('delete', old.id, old.input, old.output, old.captured_text);
+    END;
+    CREATE TRIGGER exchanges_fts_update AFTER UPDATE ON exchanges BEGIN
+        INSERT INTO exchanges_fts(exchanges_fts, rowid, input, output, captured_text)
+        VALUES ('delete', old.id, old.input, old.output, old.captured_text);
+        INSERT INTO exchanges_fts(rowid, input, output, captured_text)
+        VALUES (new.id, new.input, new.output, new.captured_text);
+    END;
+",
+];
+
+/// Conversation titles are the start of the first captured text, or of the first prompt
+const TITLE_LENGTH: usize = 80;
+
+/// Marks the start of a matched term in search snippets
+pub const MATCH_START: &str = "\u{2}";
+
+/// Marks the end of a matched term in search snippets
+pub const MATCH_END: &str = "\u{3}";
+
+/// Words of context kept around matches in search snippets
+const SNIPPET_WORDS: i64 = 16;
+
+/// A conversation as shown in the history list
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationSummary {
+    pub id: i64,
+    pub title: String,
+    /// Milliseconds since the Unix epoch
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub exchange_count: i64,
+    /// Model of the latest exchange
+    pub model: String,
+    /// Template of the first exchange
+    pub template: Option<String>,
+}
+
+/// A stored conversation with all of its exchanges, oldest first
+#[derive(Debug, Clone, Serialize)]
+pub struct Conversation {
+    pub id: i64,
+    pub title: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub exchanges: Vec<Exchange>,
+}
+
+/// One prompt and the response to it
+#[derive(Debug, Clone, Serialize)]
+pub struct Exchange {
+    pub id: i64,
+    /// "popup" or "background"
+    pub source: String,
+    pub template: Option<String>,
+    pub model: String,
+    /// The prompt
+    pub input: String,
+    /// Text captured from the focused app and sent ahead of the prompt
+    pub captured_text: String,
+    pub output: String,
+    /// Paths of the screenshots sent with the input
+    pub screenshots: Vec<String>,
+    pub started_at: i64,
+    pub finished_at: i64,
+}
+
+/// A message of a stored conversation, in the shape the popup displays
+#[derive(Debug, Clone, Serialize)]
+pub struct ResumedMessage {
+    /// "user" or "assistant"
+    pub role: &'static str,
+    pub content: String,
+    /// Screenshot data URLs sent with a user message
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+}
+
+/// Full-text search with optional filters
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SearchQuery {
+    /// Words to find in prompts, captured text and responses, matched as prefixes
+    pub query: String,
+    pub template: Option<String>,
+    pub model: Option<String>,
+    /// Only exchanges started at or after this time, in milliseconds
+    pub from: Option<i64>,
+    /// Only exchanges started before this time, in milliseconds
+    pub to: Option<i64>,
+}
+
+/// An exchange matching a search, best matches first
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub conversation_id: i64,
+    pub exchange_id: i64,
+    pub title: String,
+    pub template: Option<String>,
+    pub model: String,
+    pub started_at: i64,
+    /// Snippets with matched terms between `MATCH_START` and `MATCH_END`
+    pub input_snippet: String,
+    pub output_snippet: String,
+    /// Relevance, higher is better
+    pub score: f64,
+}
+
+/// An exchange to record, continuing `conversation_id` when it still exists
+#[derive(Debug, Clone)]
+pub struct NewExchange {
+    pub conversation_id: Option<i64>,
+    pub source: &'static str,
+    pub template: Option<String>,
+    pub model: String,
+    pub input: String,
+    pub captured_text: String,
+    pub output: String,
+    pub screenshots: Vec<String>,
+    pub started_at: i64,
+}
+
+/// Conversation history in a SQLite database, with screenshots kept next to it
+pub struct HistoryStore {
+    conn: Connection,
+    dir: PathBuf,
+}
+
+impl HistoryStore {
+    /// Opens or creates the history database in the folder
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create history folder: {}", e))?;
+
+        let conn = Connection::open(dir.join("history.db"))
+            .map_err(|e| format!("Failed to open history database: {}", e))?;
+        conn.pragma_update(None, "foreign_keys", true)
+            .map_err(|e| format!("Failed to open history database: {}", e))?;
+        migrate(&conn)?;
+
+        Ok(Self {
+            conn,
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Copies a screenshot data URL into the history folder, returning its path
+    pub fn save_screenshot(&self, data_url: &str) -> Result<String, String> {
+        screenshot_history::save(&self.screenshot_dir(), data_url)
+            .map(|path| path.to_string_lossy().to_string())
+    }
+
+    /// Reads a stored screenshot back as a data URL
+    pub fn load_screenshot(&self, path: &str) -> Result<String, String> {
+        screenshot_history::load(&self.screenshot_dir(), Path::new(path))
+    }
+
+    /// Stores an exchange, returning the id of the conversation it belongs to
+    pub fn record(&mut self, exchange: NewExchange) -> Result<i64, String> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to save history: {}", e))?;
+
+        // A conversation deleted in the meantime starts over as a new one
+        let existing = match exchange.conversation_id {
+            Some(id) => tx
+                .query_row(
+                    "SELECT id FROM conversations WHERE id = ?1",
+                    params![id],
+                    |row| row.get::<_, i64>(0),
+                )
+                .optional()
+                .map_err(|e| format!("Failed to save history: {}", e))?,
+            None => None,
+        };
+
+        let conversation_id = match existing {
+            Some(id) => {
+                tx.execute(
+                    "UPDATE conversations SET updated_at = ?1 WHERE id = ?2",
+                    params![now, id],
+                )
+                .map_err(|e| format!("Failed to save history: {}", e))?;
+                id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO conversations (title, created_at, updated_at) VALUES (?1, ?2, ?3)",
+                    params![title_for(&exchange), exchange.started_at, now],
+                )
+                .map_err(|e| format!("Failed to save history: {}", e))?;
+                tx.last_insert_rowid()
+            }
+        };
+
+        let screenshots = serde_json::to_string(&exchange.screenshots)
+            .map_err(|e| format!("Failed to save history: {}", e))?;
+        tx.execute(
+            "INSERT INTO exchanges (conversation_id, source, template, model, input, captured_text,
+                                    output, screenshots, started_at, finished_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                conversation_id,
+                exchange.source,
+                exchange.template,
+                exchange.model,
+                exchange.input,
+                exchange.captured_text,
+                exchange.output,
+                screenshots,
+                exchange.started_at,
+                now,
+            ],
+        )
+        .map_err(|e| format!("Failed to save history: {}", e))?;
+
+        tx.commit()
+            .map_err(|e| format!("Failed to save history: {}", e))?;
+
+        Ok(conversation_id)
+    }
+
+    /// Lists conversations, most recently updated first
+    pub fn list(&self, limit: usize) -> Result<Vec<ConversationSummary>, String> {
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT c.id, c.title, c.created_at, c.updated_at,
+                        (SELECT COUNT(*) FROM exchanges e WHERE e.conversation_id = c.id),
+                        (SELECT model FROM exchanges e WHERE e.conversation_id = c.id
+                         ORDER BY e.id DESC LIMIT 1),
+                        (SELECT template FROM exchanges e WHERE e.conversation_id = c.id
+                         ORDER BY e.id LIMIT 1)
+                 FROM conversations c
+                 ORDER BY c.updated_at DESC
+                 LIMIT ?1",
+            )
+            .map_err(|e| format!("Failed to read history: {}", e))?;
+
+        let rows = statement
+            .query_map(params![limit as i64], |row| {
+                Ok(ConversationSummary {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                    exchange_count: row.get(4)?,
+                    model: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+                    template: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to read history: {}", e))?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read history: {}", e))
+    }
+
+    /// Finds exchanges whose prompt, captured text or response contains every word of the query
+    pub fn search(&self, search: &SearchQuery, limit: usize) -> Result<Vec<SearchHit>, String> {
+        let Some(fts_query) = fts_query(&search.query) else {
+            return Ok(Vec::new());
+        };
+
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT e.conversation_id, e.id, c.title, e.template, e.model, e.started_at,
+                        snippet(exchanges_fts, 0, ?2, ?3, '…', ?4),
+                        snippet(exchanges_fts, 1, ?2, ?3, '…', ?4),
+                        bm25(exchanges_fts)
+                 FROM exchanges_fts
+                 JOIN exchanges e ON e.id = exchanges_fts.rowid
+                 JOIN conversations c ON c.id = e.conversation_id
+                 WHERE exchanges_fts MATCH ?1
+                   AND (?5 IS NULL OR e.template = ?5)
+                   AND (?6 IS NULL OR e.model = ?6)
+                   AND (?7 IS NULL OR e.started_at >= ?7)
+                   AND (?8 IS NULL OR e.started_at < ?8)
+                 ORDER BY bm25(exchanges_fts)
+                 LIMIT ?9",
+            )
+            .map_err(|e| format!("Failed to search history: {}", e))?;
+
+        let rows = statement
+            .query_map(
+                params![
+                    fts_query,
+                    MATCH_START,
+                    MATCH_END,
+                    SNIPPET_WORDS,
+                    search.template,
+                    search.model,
+                    search.from,
+                    search.to,
+                    limit as i64,
+                ],
+                |row| {
+                    Ok(SearchHit {
+                        conversation_id: row.get(0)?,
+                        exchange_id: row.get(1)?,
+                        title: row.get(2)?,
+                        template: row.get(3)?,
+                        model: row.get(4)?,
+                        started_at: row.get(5)?,
+                        input_snippet: row.get(6)?,
+                        output_snippet: row.get(7)?,
+                        // bm25 is lower for better matches
+                        score: -row.get::<_, f64>(8)?,
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to search history: {}", e))?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to search history: {}", e))
+    }
+
+    /// Loads a conversation with its exchanges
+    pub fn get(&self, id: i64) -> Result<Conversation, String> {
+        let (title, created_at, updated_at) = self
+            .conn
+            .query_row(
+                "SELECT title, created_at, updated_at FROM conversations WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read history: {}", e))?
+            .ok_or_else(|| "Conversation not found".to_string())?;
+
+        Ok(Conversation {
+            id,
+            title,
+            created_at,
+            updated_at,
+            exchanges: self.exchanges(id)?,
+        })
+    }
+
+    /// Rebuilds a conversation as messages, oldest first, with its screenshots loaded back
+    pub fn messages(&self, id: i64) -> Result<Vec<ResumedMessage>, String> {
+        let conversation = self.get(id)?;
+
+        let mut messages = Vec::new();
+        for exchange in conversation.exchanges {
+            // A screenshot deleted from disk shouldn't hide the rest of the conversation
+            let images = exchange
+                .screenshots
+                .iter()
+                .filter_map(|path| match self.load_screenshot(path) {
+                    Ok(image) => Some(image),
+                    Err(e) => {
+                        eprintln!("Warning: Failed to load history screenshot: {}", e);
+                        None
+                    }
+                })
+                .collect();
+
+            // Captured text went out as its own message ahead of the prompt
+            if !exchange.captured_text.is_empty() {
+                messages.push(ResumedMessage {
+                    role: "user",
+                    content: exchange.captured_text,
+                    images: Vec::new(),
+                });
+            }
+            messages.push(ResumedMessage {
+                role: "user",
+                content: exchange.input,
+                images,
+            });
+            messages.push(ResumedMessage {
+                role: "assistant",
+                content: exchange.output,
+                images: Vec::new(),
+            });
+        }
+
+        Ok(messages)
+    }
+
+    /// Deletes a conversation and its screenshots
+    pub fn delete(&mut self, id: i64) -> Result<(), String> {
+        let screenshots: Vec<String> = self
+            .exchanges(id)?
+            .into_iter()
+            .flat_map(|exchange| exchange.screenshots)
+            .collect();
+
+        self.conn
+            .execute("DELETE FROM conversations WHERE id = ?1", params![id])
+            .map_err(|e| format!("Failed to delete history: {}", e))?;
+
+        remove_files(&screenshots);
+        Ok(())
+    }
+
+    /// Deletes the least recently updated conversations beyond `keep` (0 keeps everything)
+    pub fn enforce_retention(&mut self, keep: usize) -> Result<(), String> {
+        if keep == 0 {
+            return Ok(());
+        }
+
+        let ids = {
+            let mut statement = self
+                .conn
+                .prepare(
+                    "SELECT c.id FROM conversations c
+                     ORDER BY c.updated_at DESC,
+                              (SELECT MAX(e.id) FROM exchanges e
+                               WHERE e.conversation_id = c.id) DESC
+                     LIMIT -1 OFFSET ?1",
+                )
+                .map_err(|e| format!("Failed to read history: {}", e))?;
+            let rows = statement
+                .query_map(params![keep as i64], |row| row.get::<_, i64>(0))
+                .map_err(|e| format!("Failed to read history: {}", e))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Failed to read history: {}", e))?
+        };
+
+        for id in ids {
+            self.delete(id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the oldest stored screenshots beyond `keep` (0 keeps everything)
+    pub fn enforce_screenshot_retention(&self, keep: usize) -> Result<(), String> {
+        screenshot_history::enforce_retention(&self.screenshot_dir(), keep)
+    }
+
+    /// Deletes every conversation and stored screenshot
+    pub fn clear(&mut self) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM conversations", [])
+            .map_err(|e| format!("Failed to clear history: {}", e))?;
+
+        match std::fs::remove_dir_all(self.screenshot_dir()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to delete history screenshots: {}", e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn exchanges(&self, conversation_id: i64) -> Result<Vec<Exchange>, String> {
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT id, source, template, model, input, captured_text, output, screenshots,
+                        started_at, finished_at
+                 FROM exchanges WHERE conversation_id = ?1 ORDER BY id",
+            )
+            .map_err(|e| format!("Failed to read history: {}", e))?;
+
+        let rows = statement
+            .query_map(params![conversation_id], |row| {
+                let screenshots: String = row.get(7)?;
+                Ok(Exchange {
+                    id: row.get(0)?,
+                    source: row.get(1)?,
+                    template: row.get(2)?,
+                    model: row.get(3)?,
+                    input: row.get(4)?,
+                    captured_text: row.get(5)?,
+                    output: row.get(6)?,
+                    screenshots: serde_json::from_str(&screenshots).unwrap_or_default(),
+                    started_at: row.get(8)?,
+                    finished_at: row.get(9)?,
+                })
+            })
+            .map_err(|e| format!("Failed to read history: {}", e))?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read history: {}", e))
+    }
+
+    fn screenshot_dir(&self) -> PathBuf {
+        self.dir.join("screenshots")
+    }
+}
+
+fn migrate(conn: &Connection) -> Result<(), String> {
+    let version: usize = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("Failed to read history schema: {}", e))?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        conn.execute_batch(migration)
+            .and_then(|_| conn.pragma_update(None, "user_version", index + 1))
+            .map_err(|e| format!("Failed to update history schema: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Turns typed words into an FTS5 query of quoted prefixes, so punctuation
+/// and operators in the search box are matched literally
+fn fts_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+fn title_for(exchange: &NewExchange) -> String {
+    let source = if exchange.captured_text.trim().is_empty() {
+        &exchange.input
+    } else {
+        &exchange.captured_text
+    };
+    let text = source.split_whitespace().collect::<Vec<_>>().join(" ");
+    let title = match text.char_indices().nth(TITLE_LENGTH) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    };
+
+    if title.is_empty() {
+        exchange
+            .template
+            .clone()
+            .unwrap_or_else(|| "Untitled".to_string())
+    } else {
+        title
+    }
+}
+
+fn remove_files(paths: &[String]) {
+    for path in paths {
+        if let Err(e) = std::fs::remove_file(path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("Warning: Failed to delete history screenshot: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opens a store in a temporary folder, deleted when the returned guard is dropped
+    fn temp_store() -> (tempfile::TempDir, HistoryStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::open(dir.path()).unwrap();
+        (dir, store)
+    }
+
+    fn exchange(conversation_id: Option<i64>, input: &str) -> NewExchange {
+        NewExchange {
+            conversation_id,
+            source: "popup",
+            template: None,
+            model: "gpt-4o".to_string(),
+            input: input.to_string(),
+            captured_text: String::new(),
+            output: format!("Answer to {}", input),
+            screenshots: Vec::new(),
+            started_at: 1_000,
+        }
+    }
+
+    #[test]
+    fn records_follow_ups_in_the_same_conversation() {
+        let (_dir, mut store) = temp_store();
+        let id = store.record(exchange(None, "First question")).unwrap();
+        let same = store.record(exchange(Some(id), "Follow-up")).unwrap();
+
+        assert_eq!(id, same);
+        let conversation = store.get(id).unwrap();
+        assert_eq!(conversation.title, "First question");
+        assert_eq!(
+            conversation
+                .exchanges
+                .iter()
+                .map(|e| e.input.as_str())
+                .collect::<Vec<_>>(),
+            vec!["First question", "Follow-up"]
+        );
+    }
+
+    #[test]
+    fn deleted_conversations_start_over() {
+        let (_dir, mut store) = temp_store();
+        let id = store.record(exchange(None, "Question")).unwrap();
+        store.delete(id).unwrap();
+
+        assert!(store.get(id).is_err());
+        let new_id = store.record(exchange(Some(id), "Again")).unwrap();
+        assert_eq!(store.get(new_id).unwrap().title, "Again");
+    }
+
+    #[test]
+    fn lists_conversations_with_summaries() {
+        let (_dir, mut store) = temp_store();
+        let first = store.record(exchange(None, "One")).unwrap();
+        store.record(exchange(Some(first), "Two")).unwrap();
+        store.record(exchange(None, "Other")).unwrap();
+
+        let list = store.list(10).unwrap();
+        assert_eq!(list.len(), 2);
+        let summary = list.iter().find(|c| c.id == first).unwrap();
+        assert_eq!(summary.exchange_count, 2);
+        assert_eq!(summary.model, "gpt-4o");
+
+        store.clear().unwrap();
+        assert!(store.list(10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn retention_keeps_the_most_recent_conversations() {
+        let (_dir, mut store) = temp_store();
+        let oldest = store.record(exchange(None, "Oldest")).unwrap();
+        let path = store
+            .save_screenshot("data:image/png;base64,aGVsbG8=")
+            .unwrap();
+        let older = store
+            .record(NewExchange {
+                screenshots: vec![path.clone()],
+                ..exchange(None, "Older")
+            })
+            .unwrap();
+        let newer = store.record(exchange(None, "Newer")).unwrap();
+        // A follow-up moves the oldest conversation to the front
+        store.record(exchange(Some(oldest), "Follow-up")).unwrap();
+
+        store.enforce_retention(0).unwrap();
+        assert_eq!(store.list(10).unwrap().len(), 3);
+
+        store.enforce_retention(2).unwrap();
+        let mut kept: Vec<i64> = store.list(10).unwrap().iter().map(|c| c.id).collect();
+        kept.sort();
+        assert_eq!(kept, vec![oldest, newer]);
+        assert!(store.get(older).is_err());
+        assert!(!Path::new(&path).exists());
+    }
+
+    #[test]
+    fn rebuilds_messages_with_screenshots() {
+        let (_dir, mut store) = temp_store();
+        let screenshot = "data:image/png;base64,aGVsbG8=";
+        let path = store.save_screenshot(screenshot).unwrap();
+        let id = store
+            .record(NewExchange {
+                screenshots: vec![path, "/missing/screenshot.png".to_string()],
+                ..exchange(None, "What is this?")
+            })
+            .unwrap();
+        store.record(exchange(Some(id), "And now?")).unwrap();
+
+        let messages = store.messages(id).unwrap();
+        assert_eq!(
+            messages
+                .iter()
+                .map(|m| (m.role, m.content.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("user", "What is this?"),
+                ("assistant", "Answer to What is this?"),
+                ("user", "And now?"),
+                ("assistant", "Answer to And now?"),
+            ]
+        );
+        assert_eq!(messages[0].images, vec![screenshot]);
+        assert!(messages[2].images.is_empty());
+    }
+
+    #[test]
+    fn searches_prompts_and_responses() {
+        let (_dir, mut store) = temp_store();
+        let regex = store
+            .record(exchange(None, "Explain this regex: ^a+$"))
+            .unwrap();
+        store.record(exchange(None, "Translate hello")).unwrap();
+
+        let hits = store
+            .search(
+                &SearchQuery {
+                    query: "regex expl".to_string(),
+                    ..Default::default()
+                },
+                10,
+            )
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].conversation_id, regex);
+        assert!(hits[0]
+            .input_snippet
+            .contains(&format!("{}regex{}", MATCH_START, MATCH_END)));
+
+        // Responses are searched too, and operators are taken literally
+        let hits = store
+            .search(
+                &SearchQuery {
+                    query: "answer \"hello".to_string(),
+                    ..Default::default()
+                },
+                10,
+            )
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].title, "Translate hello");
+    }
+
+    #[test]
+    fn search_applies_filters() {
+        let (_dir, mut store) = temp_store();
+        store.record(exchange(None, "Summarize report")).unwrap();
+        store
+            .record(NewExchange {
+                template: Some("Summary".to_string()),
+                model: "claude".to_string(),
+                started_at: 5_000,
+                ..exchange(None, "Summarize notes")
+            })
+            .unwrap();
+
+        let search = |search: SearchQuery| store.search(&search, 10).unwrap().len();
+        let query = || SearchQuery {
+            query: "summarize".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(search(query()), 2);
+        assert_eq!(
+            search(SearchQuery {
+                template: Some("Summary".to_string()),
+                ..query()
+            }),
+            1
+        );
+        assert_eq!(
+            search(SearchQuery {
+                model: Some("gpt-4o".to_string()),
+                ..query()
+            }),
+            1
+        );
+        assert_eq!(
+            search(SearchQuery {
+                from: Some(2_000),
+                to: Some(6_000),
+                ..query()
+            }),
+            1
+        );
+    }
+
+    #[test]
+    fn keeps_captured_text_apart_from_the_prompt() {
+        let (_dir, mut store) = temp_store();
+        let id = store
+            .record(NewExchange {
+                template: Some("Translate".to_string()),
+                captured_text: "Bonjour le monde".to_string(),
+                ..exchange(None, "Translate to English")
+            })
+            .unwrap();
+
+        let conversation = store.get(id).unwrap();
+        assert_eq!(conversation.title, "Bonjour le monde");
+        assert_eq!(conversation.exchanges[0].input, "Translate to English");
+        assert_eq!(conversation.exchanges[0].captured_text, "Bonjour le monde");
+
+        let messages = store.messages(id).unwrap();
+        assert_eq!(
+            messages
+                .iter()
+                .map(|m| (m.role, m.content.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("user", "Bonjour le monde"),
+                ("user", "Translate to English"),
+                ("assistant", "Answer to Translate to English"),
+            ]
+        );
+
+        let hits = store
+            .search(
+                &SearchQuery {
+                    query: "bonjour".to_string(),
+                    ..Default::default()
+                },
+                10,
+            )
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[test]
+    fn deleted_exchanges_leave_the_search_index() {
+        let (_dir, mut store) = temp_store();
+        let id = store.record(exchange(None, "Forget me")).unwrap();
+        store.delete(id).unwrap();
+
+        let hits = store
+            .search(
+                &SearchQuery {
+                    query: "forget".to_string(),
+                    ..Default::default()
+                },
+                10,
+            )
+            .unwrap();
+        assert!(hits.is_empty());
+    }
+}
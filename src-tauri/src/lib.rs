@@ -1,12 +1,23 @@
+mod active_window;
+mod annotation;
+mod attachments;
 mod clipboard;
 mod config;
+mod history;
+mod ocr;
+mod redaction;
 mod screenshot;
+mod screenshot_history;
+mod scroll_capture;
 
+use active_window::ActiveApp;
+use attachments::Attachment;
 use auto_launch::AutoLaunch;
-use config::{AppConfig, QuestionTemplate};
+use config::{AppConfig, ModelConfig, QuestionTemplate, ScreenshotConfig};
 use enigo::Direction::{Click, Press, Release};
 use enigo::{Enigo, Key, Keyboard, Settings};
 use futures::StreamExt;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tauri::ipc::Channel;
 use tauri::menu::{CheckMenuItem, Menu, MenuItem};
@@ -16,7 +27,14 @@ use tauri_plugin_store::StoreExt;
 use tokio::sync::Mutex;
 
 // Captured text state
-struct CapturedText(Arc<Mutex<String>>);
+struct CapturedText(Arc<Mutex<CapturedInput>>);
+
+// Captured text and the window focused when it was captured
+#[derive(Default)]
+struct CapturedInput {
+    text: String,
+    window: Option<ActiveApp>,
+}
 
 // Popup pinned state
 struct PopupPinned(Arc<Mutex<bool>>);
@@ -24,9 +42,30 @@ struct PopupPinned(Arc<Mutex<bool>>);
 // Screenshots state
 struct Screenshots(Arc<Mutex<Vec<String>>>);
 
+// Attached documents state
+struct Attachments(Arc<Mutex<Vec<Attachment>>>);
+
+// Conversation history database, unset if it couldn't be opened
+struct History(Arc<Mutex<Option<history::HistoryStore>>>);
+
+// What the screenshot selector's region is used for: "attach" or "ocr"
+struct SelectorMode(Arc<Mutex<String>>);
+
+// Monitor images frozen when the screenshot selector opened
+struct FrozenFrames(Arc<Mutex<Vec<screenshot::Frame>>>);
+
 // App exiting state
 struct AppExiting(Arc<Mutex<bool>>);
 
+// Hold-mode hotkey currently held down ("popup" or a template hotkey)
+struct HeldHotkey(Arc<Mutex<Option<String>>>);
+
+// Stream generation, bumped to cancel the in-flight AI response
+struct StreamGeneration(Arc<Mutex<u64>>);
+
+// Tray "Pause hotkeys" item, kept to sync its check state
+struct PauseHotkeysItem(CheckMenuItem<tauri::Wry>);
+
 // Tauri commands
 
 #[tauri::command]
@@ -34,7 +73,7 @@ async fn load_config(app: AppHandle) -> Result<AppConfig, String> {
     let store = app.store("config.json").map_err(|e| e.to_string())?;
 
     match store.get("app_config") {
-        Some(value) => serde_json::from_value(value.clone()).map_err(|e| e.to_string()),
+        Some(value) => AppConfig::from_stored(value).map_err(|e| e.to_string()),
         None => {
             // Return default config
             let default_config = AppConfig::default();
@@ -53,14 +92,11 @@ async fn load_config(app: AppHandle) -> Result<AppConfig, String> {
 
 #[tauri::command]
 async fn save_config(app: AppHandle, config: AppConfig) -> Result<(), String> {
-    let store = app.store("config.json").map_err(|e| e.to_string())?;
-
-    store.set(
-        "app_config".to_string(),
-        serde_json::to_value(&config).map_err(|e| e.to_string())?,
-    );
+    // The paused state is owned by the tray, the settings page may hold a stale copy
+    let mut config = config;
+    config.hotkeys_paused = load_config(app.clone()).await?.hotkeys_paused;
 
-    store.save().map_err(|e| e.to_string())?;
+    write_config(&app, &config)?;
 
     // Reload hotkeys after saving config
     reload_hotkeys(app).await?;
@@ -68,6 +104,18 @@ async fn save_config(app: AppHandle, config: AppConfig) -> Result<(), String> {
     Ok(())
 }
 
+// Writes the config to the store as is
+fn write_config(app: &AppHandle, config: &AppConfig) -> Result<(), String> {
+    let store = app.store("config.json").map_err(|e| e.to_string())?;
+
+    store.set(
+        "app_config".to_string(),
+        serde_json::to_value(config).map_err(|e| e.to_string())?,
+    );
+
+    store.save().map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn export_config(app: AppHandle) -> Result<String, String> {
     let config = load_config(app).await?;
@@ -112,298 +160,1328 @@ async fn import_config(app: AppHandle, config_json: String) -> Result<(), String
 async fn reload_hotkeys(app: AppHandle) -> Result<(), String> {
     #[cfg(desktop)]
     {
-        use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+        use tauri_plugin_global_shortcut::GlobalShortcutExt;
 
         // Load config
         let config = load_config(app.clone()).await?;
+        update_hotkeys_paused_indicator(&app, config.hotkeys_paused);
 
         // Unregister all existing shortcuts
         let _ = app.global_shortcut().unregister_all();
 
-        // Re-register screenshot hotkey
-        let screenshot_shortcut_str = config.hotkeys.screenshot_hotkey.as_str();
-        let screenshot_shortcut: Shortcut = screenshot_shortcut_str
-            .parse()
-            .map_err(|e| format!("Failed to parse screenshot shortcut: {:?}", e))?;
-
-        let app_for_screenshot = app.clone();
-        match app.global_shortcut().on_shortcut(
-            screenshot_shortcut.clone(),
-            move |_app, _shortcut, event| {
-                if event.state == tauri_plugin_global_shortcut::ShortcutState::Pressed {
-                    let app = app_for_screenshot.clone();
-                    tauri::async_runtime::spawn(async move {
-                        // Clear captured text when screenshot hotkey is triggered
-                        let captured_state: tauri::State<CapturedText> = app.state();
-                        *captured_state.0.lock().await = String::new();
-
-                        // Show screenshot selector window
-                        match show_screenshot_selector(app).await {
-                            Ok(_) => println!("Screenshot selector opened"),
-                            Err(e) => eprintln!("Failed to open screenshot selector: {}", e),
-                        }
-                    });
+        // Keep shortcuts released while hotkeys are paused
+        if config.hotkeys_paused {
+            println!("Hotkeys are paused, skipping registration");
+            return Ok(());
+        }
+
+        register_hotkeys(&app, &config)?;
+    }
+
+    Ok(())
+}
+
+// Registers the popup, screenshot and template hotkeys from config
+#[cfg(desktop)]
+fn register_hotkeys(app: &AppHandle, config: &AppConfig) -> Result<(), String> {
+    use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+    // Only the first action bound to a shortcut gets it, tell the user about the others
+    let conflicts = hotkey_conflicts(config);
+    if !conflicts.is_empty() {
+        eprintln!("Warning: Duplicate hotkeys: {}", conflicts.join("; "));
+        notify(app, "Duplicate hotkeys", &conflicts.join("\n"));
+    }
+
+    // Register screenshot hotkey
+    let screenshot_shortcut_str = config.hotkeys.screenshot_hotkey.as_str();
+    let screenshot_shortcut: Shortcut = screenshot_shortcut_str
+        .parse()
+        .map_err(|e| format!("Failed to parse screenshot shortcut: {:?}", e))?;
+
+    let app_for_screenshot = app.clone();
+    let screenshot_mode = config.hotkeys.screenshot_mode.clone();
+    match app.global_shortcut().on_shortcut(
+        screenshot_shortcut.clone(),
+        move |_app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed {
+                let app = app_for_screenshot.clone();
+                let mode = screenshot_mode.clone();
+                tauri::async_runtime::spawn(handle_screenshot_hotkey(app, mode));
+            }
+        },
+    ) {
+        Ok(_) => {
+            if let Err(e) = app.global_shortcut().register(screenshot_shortcut) {
+                eprintln!("Warning: Failed to register screenshot shortcut: {}", e);
+            }
+        }
+        Err(e) => {
+            eprintln!(
+                "Warning: Failed to setup screenshot shortcut handler: {}",
+                e
+            );
+        }
+    }
+
+    // Register optional hotkeys
+    register_optional_hotkey(
+        app,
+        config.hotkeys.clipboard_image_hotkey.as_deref(),
+        "clipboard image",
+        handle_clipboard_image_hotkey,
+    );
+    register_optional_hotkey(app, config.ocr.hotkey.as_deref(), "OCR", handle_ocr_hotkey);
+    register_optional_hotkey(
+        app,
+        config.hotkeys.scroll_capture_hotkey.as_deref(),
+        "scrolling capture",
+        handle_scroll_capture_hotkey,
+    );
+    register_optional_hotkey(
+        app,
+        config.hotkeys.history_search_hotkey.as_deref(),
+        "history search",
+        handle_history_search_hotkey,
+    );
+
+    // Register popup hotkey
+    let shortcut_str = config.hotkeys.popup_hotkey.as_str();
+    let shortcut: Shortcut = shortcut_str
+        .parse()
+        .map_err(|e| format!("Failed to parse shortcut: {:?}", e))?;
+
+    let app_handle = app.clone();
+    let popup_hold_mode = config.hotkeys.popup_hold_mode;
+    match app
+        .global_shortcut()
+        .on_shortcut(shortcut.clone(), move |_app, _shortcut, event| {
+            let app = app_handle.clone();
+            match event.state {
+                ShortcutState::Pressed => {
+                    tauri::async_runtime::spawn(handle_popup_hotkey(app, popup_hold_mode));
                 }
-            },
-        ) {
-            Ok(_) => {
-                if let Err(e) = app.global_shortcut().register(screenshot_shortcut) {
-                    eprintln!("Warning: Failed to register screenshot shortcut: {}", e);
+                ShortcutState::Released => {
+                    if popup_hold_mode {
+                        tauri::async_runtime::spawn(handle_hotkey_released(
+                            app,
+                            "popup".to_string(),
+                        ));
+                    }
                 }
             }
-            Err(e) => {
-                eprintln!(
-                    "Warning: Failed to setup screenshot shortcut handler: {}",
-                    e
-                );
+        }) {
+        Ok(_) => {
+            if let Err(e) = app.global_shortcut().register(shortcut) {
+                eprintln!("Warning: Failed to register global shortcut: {}", e);
             }
         }
+        Err(e) => {
+            eprintln!("Warning: Failed to setup shortcut handler: {}", e);
+        }
+    }
 
-        // Re-register popup hotkey
-        let shortcut_str = config.hotkeys.popup_hotkey.as_str();
-        let shortcut: Shortcut = shortcut_str
-            .parse()
-            .map_err(|e| format!("Failed to parse shortcut: {:?}", e))?;
-
-        let app_handle = app.clone();
-        match app
-            .global_shortcut()
-            .on_shortcut(shortcut.clone(), move |_app, _shortcut, event| {
-                if event.state == tauri_plugin_global_shortcut::ShortcutState::Pressed {
-                    let app = app_handle.clone();
-                    tauri::async_runtime::spawn(async move {
-                        // Check if popup is already visible
-                        if let Some(popup) = app.get_webview_window("popup") {
-                            if let Ok(is_visible) = popup.is_visible() {
-                                if is_visible {
-                                    // Popup is already open, emit event to trigger replace
-                                    let _ = popup.emit("trigger-replace", ());
-                                    return;
-                                }
-                            }
-                        }
+    // Group templates by hotkey, the focused app decides which one runs
+    let mut template_groups: Vec<(String, Vec<QuestionTemplate>)> = Vec::new();
+    for template in config.templates.iter() {
+        if let Some(hotkey_str) = &template.hotkey {
+            if !hotkey_str.is_empty() {
+                match template_groups.iter_mut().find(|(h, _)| h == hotkey_str) {
+                    Some((_, group)) => group.push(template.clone()),
+                    None => template_groups.push((hotkey_str.clone(), vec![template.clone()])),
+                }
+            }
+        }
+    }
 
-                        // Popup not visible, proceed with normal flow
-                        // Capture the selected text using UI Automation API
-                        match clipboard::capture_selected_text().await {
-                            Ok(text) => {
-                                // Store the captured text in state
-                                let captured_state: tauri::State<CapturedText> = app.state();
-                                *captured_state.0.lock().await = text;
-                            }
-                            Err(e) => {
-                                eprintln!("Warning: Failed to capture selection: {}", e);
+    // Register template hotkeys
+    for (hotkey_str, templates) in template_groups {
+        let app_clone = app.clone();
+        let hotkey_clone = hotkey_str.clone();
+        let hold_mode = templates.iter().any(|t| t.hold_mode);
+
+        if let Ok(template_shortcut) = hotkey_str.parse::<Shortcut>() {
+            // Shortcuts shared with another hotkey are reported by `hotkey_conflicts`
+            if app
+                .global_shortcut()
+                .is_registered(template_shortcut.clone())
+            {
+                continue;
+            }
+
+            // Register template shortcut handler
+            match app.global_shortcut().on_shortcut(
+                template_shortcut.clone(),
+                move |_app, _shortcut, event| {
+                    let app = app_clone.clone();
+                    let hotkey = hotkey_clone.clone();
+                    match event.state {
+                        ShortcutState::Pressed => {
+                            tauri::async_runtime::spawn(handle_template_hotkey(
+                                app,
+                                hotkey,
+                                templates.clone(),
+                            ));
+                        }
+                        ShortcutState::Released => {
+                            if hold_mode {
+                                tauri::async_runtime::spawn(handle_hotkey_released(app, hotkey));
                             }
                         }
+                    }
+                },
+            ) {
+                Ok(_) => {
+                    if let Err(e) = app.global_shortcut().register(template_shortcut) {
+                        eprintln!(
+                            "Warning: Failed to register template shortcut {}: {}",
+                            hotkey_str, e
+                        );
+                    }
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Warning: Failed to setup template shortcut handler {}: {}",
+                        hotkey_str, e
+                    );
+                }
+            }
+        } else {
+            eprintln!("Warning: Failed to parse template hotkey: {}", hotkey_str);
+        }
+    }
+
+    Ok(())
+}
 
-                        // Show the popup window
-                        let _ = show_popup_window(app).await;
-                    });
+async fn handle_screenshot_hotkey(app: AppHandle, mode: String) {
+    // Clear captured text when screenshot hotkey is triggered
+    start_capture(&app).await;
+
+    // Tell the user before selecting a region that can't be captured
+    if let Err(e) = screenshot_redactor(&app).await {
+        notify(&app, "Ask Anywhere", &e);
+        return;
+    }
+
+    if mode == "window" {
+        // Attach the whole window under the cursor without a selection step
+        let (x, y) = cursor_point(&app);
+        let options = screenshot_options(&app).await;
+        let captured = match screenshot_redactor(&app).await {
+            Ok(redactor) => screenshot::capture_window_at(x, y, options, redactor).await,
+            Err(e) => Err(e),
+        };
+        match captured {
+            Ok(screenshot_data) => {
+                save_to_history(&app, &screenshot_data).await;
+                let screenshots_state: tauri::State<Screenshots> = app.state();
+                screenshots_state.0.lock().await.push(screenshot_data);
+
+                if let Err(e) = show_popup_window(app).await {
+                    eprintln!("Failed to show popup window: {}", e);
                 }
-            }) {
-            Ok(_) => {
-                if let Err(e) = app.global_shortcut().register(shortcut) {
-                    eprintln!("Warning: Failed to register global shortcut: {}", e);
+            }
+            Err(e) => eprintln!("Failed to capture window: {}", e),
+        }
+        return;
+    }
+
+    // Show screenshot selector window
+    match open_screenshot_selector(app, "attach").await {
+        Ok(_) => println!("Screenshot selector opened"),
+        Err(e) => eprintln!("Failed to open screenshot selector: {}", e),
+    }
+}
+
+// Lists shortcuts bound to more than one action, in registration order
+#[cfg(desktop)]
+fn hotkey_conflicts(config: &AppConfig) -> Vec<String> {
+    use tauri_plugin_global_shortcut::Shortcut;
+
+    let hotkeys = &config.hotkeys;
+    let fixed = [
+        ("screenshot", Some(hotkeys.screenshot_hotkey.as_str())),
+        ("clipboard image", hotkeys.clipboard_image_hotkey.as_deref()),
+        ("OCR", config.ocr.hotkey.as_deref()),
+        (
+            "scrolling capture",
+            hotkeys.scroll_capture_hotkey.as_deref(),
+        ),
+        ("history search", hotkeys.history_search_hotkey.as_deref()),
+        ("popup", Some(hotkeys.popup_hotkey.as_str())),
+    ];
+    let mut bindings: Vec<(String, &str)> = fixed
+        .into_iter()
+        .filter_map(|(name, hotkey)| Some((name.to_string(), hotkey?)))
+        .collect();
+    // Templates sharing a hotkey are one binding, the focused app picks between them
+    for template in config.templates.iter() {
+        if let Some(hotkey) = template.hotkey.as_deref() {
+            if !bindings
+                .iter()
+                .any(|(name, bound)| name.starts_with("template") && *bound == hotkey)
+            {
+                bindings.push((format!("template \"{}\"", template.name), hotkey));
+            }
+        }
+    }
+
+    let mut claimed: Vec<(Shortcut, &str)> = Vec::new();
+    let mut conflicts = Vec::new();
+    for (name, hotkey) in bindings.iter() {
+        if hotkey.trim().is_empty() {
+            continue;
+        }
+        let Ok(shortcut) = hotkey.parse::<Shortcut>() else {
+            continue;
+        };
+        match claimed.iter().find(|(claimed, _)| *claimed == shortcut) {
+            Some((_, owner)) => conflicts.push(format!(
+                "{} is already used by the {} hotkey, the {} hotkey is disabled",
+                hotkey, owner, name
+            )),
+            None => claimed.push((shortcut, name.as_str())),
+        }
+    }
+    conflicts
+}
+
+// Registers a press-only hotkey that may be left unset, logging instead of failing
+#[cfg(desktop)]
+fn register_optional_hotkey<F, Fut>(app: &AppHandle, hotkey: Option<&str>, name: &str, handler: F)
+where
+    F: Fn(AppHandle) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+    let Some(hotkey) = hotkey.filter(|hotkey| !hotkey.trim().is_empty()) else {
+        return;
+    };
+
+    let shortcut: Shortcut = match hotkey.parse() {
+        Ok(shortcut) => shortcut,
+        Err(e) => {
+            eprintln!("Warning: Failed to parse {} shortcut: {:?}", name, e);
+            return;
+        }
+    };
+
+    let app_handle = app.clone();
+    match app
+        .global_shortcut()
+        .on_shortcut(shortcut.clone(), move |_app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed {
+                tauri::async_runtime::spawn(handler(app_handle.clone()));
+            }
+        }) {
+        Ok(_) => {
+            if let Err(e) = app.global_shortcut().register(shortcut) {
+                eprintln!("Warning: Failed to register {} shortcut: {}", name, e);
+            }
+        }
+        Err(e) => {
+            eprintln!("Warning: Failed to setup {} shortcut handler: {}", name, e);
+        }
+    }
+}
+
+async fn handle_ocr_hotkey(app: AppHandle) {
+    // Select a region whose text is copied instead of attached
+    match open_screenshot_selector(app, "ocr").await {
+        Ok(_) => println!("OCR selector opened"),
+        Err(e) => eprintln!("Failed to open screenshot selector: {}", e),
+    }
+}
+
+async fn handle_scroll_capture_hotkey(app: AppHandle) {
+    if let Err(e) = screenshot_redactor(&app).await {
+        notify(&app, "Ask Anywhere", &e);
+        return;
+    }
+
+    // Select the region to capture while scrolling
+    match open_screenshot_selector(app, "scroll").await {
+        Ok(_) => println!("Scrolling capture selector opened"),
+        Err(e) => eprintln!("Failed to open screenshot selector: {}", e),
+    }
+}
+
+async fn handle_history_search_hotkey(app: AppHandle) {
+    match show_history_search_window(app).await {
+        Ok(_) => println!("History search opened"),
+        Err(e) => eprintln!("Failed to open history search: {}", e),
+    }
+}
+
+async fn handle_clipboard_image_hotkey(app: AppHandle) {
+    // Clear captured text so the image is asked about on its own
+    start_capture(&app).await;
+
+    let screenshots_state: tauri::State<Screenshots> = app.state();
+    if let Err(e) = paste_clipboard_image(app.clone(), screenshots_state).await {
+        notify(&app, "Ask Anywhere", &e);
+        return;
+    }
+
+    if let Err(e) = show_popup_window(app).await {
+        eprintln!("Failed to show popup window: {}", e);
+    }
+}
+
+async fn handle_popup_hotkey(app: AppHandle, hold_mode: bool) {
+    // Check if popup is already visible
+    if let Some(popup) = app.get_webview_window("popup") {
+        if let Ok(is_visible) = popup.is_visible() {
+            if is_visible {
+                if hold_mode {
+                    // Hold mode: the replace is confirmed when the hotkey is released
+                    let held_state: tauri::State<HeldHotkey> = app.state();
+                    *held_state.0.lock().await = Some("popup".to_string());
+                } else {
+                    // Popup is already open, emit event to trigger replace
+                    let _ = popup.emit("trigger-replace", ());
                 }
+                return;
             }
+        }
+    }
+
+    // Popup not visible, proceed with normal flow
+    let active_app = start_capture(&app).await;
+
+    // Capture the selected text using UI Automation API
+    match clipboard::capture_selected_text(&active_app).await {
+        Ok(captured) => {
+            // Store the captured text in state
+            let captured_state: tauri::State<CapturedText> = app.state();
+            captured_state.0.lock().await.text = captured.text;
+
+            // Files selected in a file manager become attachments
+            if !captured.files.is_empty() {
+                let (documents, images) = load_captured_files(&app, captured.files).await;
+                store_attachments(&app, documents, images).await;
+            }
+        }
+        Err(e) => {
+            eprintln!("Warning: Failed to capture selection: {}", e);
+        }
+    }
+
+    // Show the popup window
+    let _ = show_popup_window(app).await;
+}
+
+async fn handle_template_hotkey(app: AppHandle, hotkey: String, templates: Vec<QuestionTemplate>) {
+    // Ignore key repeats while a hold-mode hotkey is still held
+    if is_hotkey_held(&app, &hotkey).await {
+        return;
+    }
+
+    // Dispatch to the template matching the focused application
+    let active_app = start_capture(&app).await;
+
+    let mut template = match active_window::select_template(&templates, &active_app) {
+        Some(template) => template.clone(),
+        None => {
+            println!(
+                "No template for hotkey {} matches app {}",
+                hotkey, active_app.app_name
+            );
+            return;
+        }
+    };
+
+    template.prompt = active_window::apply_placeholders(&template.prompt, &active_app);
+
+    if template.hold_mode {
+        let held_state: tauri::State<HeldHotkey> = app.state();
+        *held_state.0.lock().await = Some(hotkey.clone());
+    }
+
+    // Capture the selected text
+    let input = clipboard::capture_template_input(&app, &active_app, &template).await;
+    let (mut captured_text, files) = match input {
+        Ok(captured) => {
+            // Store the captured text in state
+            let captured_state: tauri::State<CapturedText> = app.state();
+            captured_state.0.lock().await.text = captured.text.clone();
+            (captured.text, captured.files)
+        }
+        Err(e) => {
+            eprintln!("Warning: Failed to capture selection: {}", e);
+            (String::new(), Vec::new())
+        }
+    };
+
+    // Files selected in a file manager become the input
+    let run_in_background = template.background_mode && !template.hold_mode;
+    let mut has_files = false;
+    if !files.is_empty() {
+        let (documents, images) = load_captured_files(&app, files).await;
+
+        if run_in_background {
+            // Background requests are text only, so documents go into the prompt
+            if !images.is_empty() {
+                eprintln!("Warning: Images are not sent in background mode");
+            }
+            if let Err(e) = attachments::check_limits(&documents, &[]) {
+                notify(&app, &template.name, &e);
+                clear_held_hotkey(&app, &hotkey).await;
+                return;
+            }
+            has_files = !documents.is_empty();
+            captured_text = attachments::format_for_prompt(&documents);
+        } else {
+            has_files = !documents.is_empty() || !images.is_empty();
+            store_attachments(&app, documents, images).await;
+        }
+    }
+
+    if captured_text.is_empty() && !has_files {
+        if template.input_source == "ask" {
+            // Let the user type the input for this template in the popup
+            clear_held_hotkey(&app, &hotkey).await;
+            ask_template_input(app, template).await;
+            return;
+        }
+
+        if run_in_background {
+            // Don't waste an API call on a bare prompt
+            notify(
+                &app,
+                &template.name,
+                "Nothing is selected, so the template was not run.",
+            );
+            clear_held_hotkey(&app, &hotkey).await;
+            return;
+        }
+    }
+
+    // Hold mode always previews the answer in the popup
+    if run_in_background {
+        // Background mode: execute without showing popup
+        println!("Executing template {} in background mode", template.id);
+        run_template_in_background(app, template, captured_text, active_app).await;
+    } else {
+        // Released while the selection was captured, so don't open the popup at all
+        if template.hold_mode && !is_hotkey_held(&app, &hotkey).await {
+            println!("Template {} released before execution", template.id);
+            return;
+        }
+
+        // Normal mode: show popup and emit event
+        // Show the popup window with template info
+        if let Err(e) = show_popup_window(app.clone()).await {
+            eprintln!("Failed to show popup: {}", e);
+            return;
+        }
+
+        // Wait a bit for the window to be fully loaded
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+
+        // Released before anything was sent, nothing to cancel but the empty popup
+        if template.hold_mode && !is_hotkey_held(&app, &hotkey).await {
+            println!("Template {} released before execution", template.id);
+            if let Some(popup) = app.get_webview_window("popup") {
+                let _ = popup.hide();
+            }
+            return;
+        }
+
+        // Emit event to trigger template execution
+        if let Some(popup) = app.get_webview_window("popup") {
+            println!(
+                "Emitting execute-template event for template: {}",
+                template.id
+            );
+            if let Err(e) = popup.emit(
+                "execute-template",
+                serde_json::json!({
+                    "id": template.id,
+                    "prompt": template.prompt,
+                    "action": template.action,
+                    "hold": template.hold_mode,
+                    "hotkey": hotkey,
+                }),
+            ) {
+                eprintln!("Failed to emit execute-template event: {}", e);
+            } else {
+                println!("Successfully emitted execute-template event");
+            }
+        } else {
+            eprintln!("Popup window not found when trying to emit event");
+        }
+    }
+}
+
+// Opens the popup prefilled with a template command awaiting the user's input
+async fn ask_template_input(app: AppHandle, template: QuestionTemplate) {
+    if let Err(e) = show_popup_window(app.clone()).await {
+        eprintln!("Failed to show popup: {}", e);
+        return;
+    }
+
+    // Wait a bit for the window to be fully loaded
+    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+
+    if let Some(popup) = app.get_webview_window("popup") {
+        if let Err(e) = popup.emit(
+            "prefill-template",
+            serde_json::json!({ "id": template.id, "name": template.name }),
+        ) {
+            eprintln!("Failed to emit prefill-template event: {}", e);
+        }
+    }
+}
+
+// Shows a system notification
+fn notify(app: &AppHandle, title: &str, body: &str) {
+    use tauri_plugin_notification::NotificationExt;
+
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        eprintln!("Failed to show notification: {}", e);
+    }
+}
+
+// Reads files captured from a file manager, split into documents and image data URLs
+async fn load_captured_files(
+    app: &AppHandle,
+    files: Vec<PathBuf>,
+) -> (Vec<Attachment>, Vec<String>) {
+    let options = screenshot_options(app).await;
+    // Documents are still attached when images can't be redacted
+    let (redactor, redaction_error) = match screenshot_redactor(app).await {
+        Ok(redactor) => (redactor, None),
+        Err(e) => (None, Some(e)),
+    };
+    let loaded = tokio::task::spawn_blocking(move || {
+        files
+            .iter()
+            .map(|path| attachments::load_file(path, &options, redactor.as_ref()))
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    let mut documents = Vec::new();
+    let mut images = Vec::new();
+    let mut skipped_images = false;
+    for file in loaded {
+        match file {
+            Ok(attachments::LoadedFile::Document(document)) => documents.push(document),
+            Ok(attachments::LoadedFile::Image(_)) if redaction_error.is_some() => {
+                skipped_images = true
+            }
+            Ok(attachments::LoadedFile::Image(data_url)) => images.push(data_url),
+            Err(e) => eprintln!("Warning: Failed to attach file: {}", e),
+        }
+    }
+    if skipped_images {
+        notify(app, "Ask Anywhere", &redaction_error.unwrap_or_default());
+    }
+
+    (documents, images)
+}
+
+// Adds documents to the attachments state and images to the screenshots state
+async fn store_attachments(app: &AppHandle, documents: Vec<Attachment>, images: Vec<String>) {
+    let attachments_state: tauri::State<Attachments> = app.state();
+    attachments_state.0.lock().await.extend(documents);
+
+    let screenshots_state: tauri::State<Screenshots> = app.state();
+    screenshots_state.0.lock().await.extend(images);
+}
+
+// Loads the screenshot encoding options, falling back to the defaults
+async fn screenshot_options(app: &AppHandle) -> ScreenshotConfig {
+    match load_config(app.clone()).await {
+        Ok(config) => config.screenshot,
+        Err(e) => {
+            eprintln!("Warning: Failed to load screenshot options: {}", e);
+            ScreenshotConfig::default()
+        }
+    }
+}
+
+// Resolves the redaction settings for a capture, None when redaction is off.
+// Errors are returned so captures fail rather than leak unredacted text.
+async fn screenshot_redactor(app: &AppHandle) -> Result<Option<redaction::Redactor>, String> {
+    let config = load_config(app.clone()).await?;
+    if !config.redaction.enabled {
+        return Ok(None);
+    }
+
+    let model_dir = ocr_model_dir(app).await?;
+    if !ocr::models_available(&model_dir) {
+        return Err("Screenshots are blocked because redaction is on and the OCR models aren't downloaded. Download them in the settings or turn redaction off.".to_string());
+    }
+
+    Ok(Some(redaction::Redactor {
+        model_dir,
+        config: config.redaction,
+    }))
+}
+
+// Folder that captured screenshots are saved to
+fn screenshot_history_dir(app: &AppHandle, options: &ScreenshotConfig) -> Result<PathBuf, String> {
+    if !options.save_folder.trim().is_empty() {
+        return Ok(PathBuf::from(options.save_folder.trim()));
+    }
+
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("screenshots"))
+        .map_err(|e| format!("Failed to get app data folder: {}", e))
+}
+
+// Saves a capture to the history folder when enabled, trimming old files
+async fn save_to_history(app: &AppHandle, data_url: &str) {
+    let options = screenshot_options(app).await;
+    if !options.save_to_disk {
+        return;
+    }
+
+    let dir = match screenshot_history_dir(app, &options) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Warning: {}", e);
+            return;
+        }
+    };
+
+    // Saving happens in the background so the popup isn't delayed
+    let data_url = data_url.to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = screenshot_history::save(&dir, &data_url)
+            .and_then(|_| screenshot_history::enforce_retention(&dir, options.retention_count))
+        {
+            eprintln!("Warning: Failed to save screenshot to history: {}", e);
+        }
+    });
+}
+
+// Clears the captured text and records the focused window it will be captured from
+async fn start_capture(app: &AppHandle) -> ActiveApp {
+    let active_app = match active_window::capture_active_window().await {
+        Ok(active_app) => Some(active_app),
+        Err(e) => {
+            eprintln!("Warning: Failed to detect active window: {}", e);
+            None
+        }
+    };
+
+    let captured_state: tauri::State<CapturedText> = app.state();
+    *captured_state.0.lock().await = CapturedInput {
+        text: String::new(),
+        window: active_app.clone(),
+    };
+
+    active_app.unwrap_or_default()
+}
+
+async fn handle_hotkey_released(app: AppHandle, hotkey_id: String) {
+    // Only act on the hotkey that is currently held
+    {
+        let held_state: tauri::State<HeldHotkey> = app.state();
+        let mut held = held_state.0.lock().await;
+        if held.as_deref() != Some(hotkey_id.as_str()) {
+            return;
+        }
+        *held = None;
+    }
+
+    // The popup confirms the action if the answer is complete, otherwise cancels it
+    if let Some(popup) = app.get_webview_window("popup") {
+        if let Err(e) = popup.emit("hotkey-released", serde_json::json!({ "id": hotkey_id })) {
+            eprintln!("Failed to emit hotkey-released event: {}", e);
+        }
+    }
+}
+
+async fn clear_held_hotkey(app: &AppHandle, hotkey_id: &str) {
+    let held_state: tauri::State<HeldHotkey> = app.state();
+    let mut held = held_state.0.lock().await;
+    if held.as_deref() == Some(hotkey_id) {
+        *held = None;
+    }
+}
+
+async fn is_hotkey_held(app: &AppHandle, hotkey_id: &str) -> bool {
+    let held_state: tauri::State<HeldHotkey> = app.state();
+    let held = held_state.0.lock().await;
+    held.as_deref() == Some(hotkey_id)
+}
+
+async fn run_template_in_background(
+    app: AppHandle,
+    template: QuestionTemplate,
+    captured_text: String,
+    active_app: ActiveApp,
+) {
+    use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+
+    // Load config to get model info
+    let store = match app.store("config.json") {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to load config: {}", e);
+            return;
+        }
+    };
+
+    let config: AppConfig = match store.get("app_config") {
+        Some(value) => match AppConfig::from_stored(value) {
+            Ok(c) => c,
             Err(e) => {
-                eprintln!("Warning: Failed to setup shortcut handler: {}", e);
+                eprintln!("Failed to parse config: {}", e);
+                return;
             }
+        },
+        None => {
+            eprintln!("No config found");
+            return;
         }
+    };
+
+    let selected_model = &config.models[config.selected_model_index];
+
+    // Build the full prompt with captured text
+    let full_prompt = if !captured_text.is_empty() {
+        format!("{}\n\n{}", template.prompt, captured_text)
+    } else {
+        template.prompt.clone()
+    };
+
+    let url = if selected_model.base_url.ends_with('/') {
+        format!("{}chat/completions", selected_model.base_url)
+    } else {
+        format!("{}/chat/completions", selected_model.base_url)
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    if let Ok(auth_value) = HeaderValue::from_str(&format!("Bearer {}", selected_model.api_key)) {
+        headers.insert(AUTHORIZATION, auth_value);
+    }
+
+    let mut messages = Vec::new();
+    if config.include_window_context {
+        if let Some(context) = active_window::context_message(&active_app) {
+            messages.push(serde_json::json!({"role": "system", "content": context}));
+        }
+    }
+    messages.push(serde_json::json!({"role": "user", "content": full_prompt}));
+
+    let body = serde_json::json!({
+        "model": selected_model.model_name,
+        "messages": messages,
+        "stream": false
+    });
+
+    // Call AI API directly in background
+    let started_at = chrono::Utc::now().timestamp_millis();
+    let client = reqwest::Client::new();
+    match client.post(&url).headers(headers).json(&body).send().await {
+        Ok(response) => {
+            if response.status().is_success() {
+                if let Ok(result) = response.json::<serde_json::Value>().await {
+                    if let Some(content) = result["choices"][0]["message"]["content"].as_str() {
+                        println!(
+                            "Background execution completed. Response length: {}",
+                            content.len()
+                        );
+
+                        let exchange = history::NewExchange {
+                            conversation_id: None,
+                            source: "background",
+                            template: Some(template.name.clone()),
+                            model: selected_model.model_name.clone(),
+                            input: template.prompt.clone(),
+                            captured_text: captured_text.clone(),
+                            output: content.to_string(),
+                            screenshots: Vec::new(),
+                            started_at,
+                        };
+                        record_history(&app, exchange, &[]).await;
+
+                        // Execute action
+                        if template.action == "copy" {
+                            use tauri_plugin_clipboard_manager::ClipboardExt;
+                            if let Err(e) = app.clipboard().write_text(content) {
+                                eprintln!("Failed to copy to clipboard: {}", e);
+                            } else {
+                                println!("Copied response to clipboard");
+                            }
+                        } else if template.action == "replace" {
+                            // Use the replace_text_in_source function
+                            replace_text_in_source(app, content.to_string());
+                        }
+                    }
+                }
+            } else {
+                eprintln!("API error: {}", response.status());
+            }
+        }
+        Err(e) => {
+            eprintln!("Request failed: {}", e);
+        }
+    }
+}
+
+#[tauri::command]
+async fn set_hotkeys_paused(app: AppHandle, paused: bool) -> Result<(), String> {
+    // Persist the paused state, reloading re-registers hotkeys and updates the tray
+    let mut config = load_config(app.clone()).await?;
+    config.hotkeys_paused = paused;
+    write_config(&app, &config)?;
+
+    reload_hotkeys(app).await
+}
+
+#[tauri::command]
+async fn is_hotkeys_paused(app: AppHandle) -> Result<bool, String> {
+    let config = load_config(app).await?;
+    Ok(config.hotkeys_paused)
+}
+
+// Reflects the paused state in the tray menu and tooltip
+fn update_hotkeys_paused_indicator(app: &AppHandle, paused: bool) {
+    if let Some(item) = app.try_state::<PauseHotkeysItem>() {
+        let _ = item.0.set_checked(paused);
+    }
+
+    if let Some(tray) = app.tray_by_id("tray") {
+        let _ = tray.set_tooltip(Some(tray_tooltip(paused)));
+    }
+}
+
+fn tray_tooltip(hotkeys_paused: bool) -> &'static str {
+    if hotkeys_paused {
+        "Ask Anywhere (hotkeys paused)"
+    } else {
+        "Ask Anywhere"
+    }
+}
+
+#[tauri::command]
+async fn get_captured_text(state: State<'_, CapturedText>) -> Result<String, String> {
+    let captured = state.0.lock().await;
+    Ok(captured.text.clone())
+}
+
+// Fills a popup prompt's window placeholders and builds the window context message
+#[tauri::command]
+async fn apply_window_context(
+    app: AppHandle,
+    state: State<'_, CapturedText>,
+    prompt: String,
+) -> Result<active_window::PromptContext, String> {
+    let config = load_config(app).await?;
+    let window = state.0.lock().await.window.clone().unwrap_or_default();
+
+    Ok(active_window::prompt_context(
+        &prompt,
+        &window,
+        config.include_window_context,
+    ))
+}
+
+#[tauri::command]
+async fn take_screenshot(app: AppHandle, state: State<'_, Screenshots>) -> Result<String, String> {
+    // Capture the monitor under the cursor
+    let (x, y) = cursor_point(&app);
+    let options = screenshot_options(&app).await;
+    let redactor = screenshot_redactor(&app).await?;
+    let screenshot_data = screenshot::capture_screenshot(x, y, options, redactor).await?;
+
+    save_to_history(&app, &screenshot_data).await;
+
+    // Store in state
+    let mut screenshots = state.0.lock().await;
+    screenshots.push(screenshot_data.clone());
+
+    Ok(screenshot_data)
+}
+
+#[tauri::command]
+async fn capture_screenshot_region(
+    app: AppHandle,
+    state: State<'_, Screenshots>,
+    frozen_state: State<'_, FrozenFrames>,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    annotations: Option<Vec<annotation::Annotation>>,
+) -> Result<String, String> {
+    let region = screenshot::Region {
+        x,
+        y,
+        width,
+        height,
+    };
+    let (monitor, placement) = selector_placement(&app)?;
+    let monitor_point = monitor_center(&monitor);
+
+    // Crop from the screen as it was when the selector opened
+    let frozen = frozen_state
+        .0
+        .lock()
+        .await
+        .iter()
+        .find(|frame| frame.contains(monitor_point.0, monitor_point.1))
+        .map(|frame| frame.image.clone());
+
+    // Capture region
+    let options = screenshot_options(&app).await;
+    let redactor = screenshot_redactor(&app).await?;
+    let screenshot_data = screenshot::capture_region(
+        monitor_point,
+        region,
+        placement,
+        options,
+        redactor,
+        frozen,
+        annotations.unwrap_or_default(),
+    )
+    .await?;
+
+    save_to_history(&app, &screenshot_data).await;
+
+    // Store in state
+    let mut screenshots = state.0.lock().await;
+    screenshots.push(screenshot_data.clone());
+
+    Ok(screenshot_data)
+}
+
+#[tauri::command]
+async fn capture_scrolling_region(
+    app: AppHandle,
+    state: State<'_, Screenshots>,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+) -> Result<usize, String> {
+    let region = screenshot::Region {
+        x,
+        y,
+        width,
+        height,
+    };
+    let (monitor, placement) = selector_placement(&app)?;
+    let monitor_point = monitor_center(&monitor);
+
+    // The live screen is captured, so the selector has to be gone first
+    if let Some(selector) = app.get_webview_window("screenshot-selector") {
+        let _ = selector.close();
+    }
+    tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
+
+    // Errors can't be shown by the closed selector anymore
+    let tiles = match scroll_capture_tiles(&app, monitor_point, region, placement).await {
+        Ok(tiles) => tiles,
+        Err(e) => {
+            notify(&app, "Ask Anywhere", &e);
+            return Err(e);
+        }
+    };
+
+    for tile in &tiles {
+        save_to_history(&app, tile).await;
+    }
+
+    let count = tiles.len();
+    state.0.lock().await.extend(tiles);
+
+    // The selector closed itself above, so show the popup from here
+    show_popup_window(app).await?;
+
+    Ok(count)
+}
+
+// Captures while scrolling and encodes the result as one or more tiles
+async fn scroll_capture_tiles(
+    app: &AppHandle,
+    monitor_point: (i32, i32),
+    region: screenshot::Region,
+    placement: screenshot::Placement,
+) -> Result<Vec<String>, String> {
+    let stitched = scroll_capture::capture_scrolling(monitor_point, region, placement).await?;
+
+    // Split into tiles so each stays legible after downscaling
+    let options = screenshot_options(app).await;
+    let redactor = screenshot_redactor(app).await?;
+    tokio::task::spawn_blocking(move || {
+        scroll_capture::split_tiles(stitched, options.scroll_tile_height)
+            .iter()
+            .map(|tile| screenshot::encode_image(tile, &options, redactor.as_ref()))
+            .collect::<Result<Vec<_>, String>>()
+    })
+    .await
+    .map_err(|e| format!("Screenshot task failed: {}", e))?
+}
+
+#[tauri::command]
+async fn capture_region_text(
+    app: AppHandle,
+    frozen_state: State<'_, FrozenFrames>,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+) -> Result<String, String> {
+    let region = screenshot::Region {
+        x,
+        y,
+        width,
+        height,
+    };
+    let (monitor, placement) = selector_placement(&app)?;
+    let monitor_point = monitor_center(&monitor);
+
+    let frozen = frozen_state
+        .0
+        .lock()
+        .await
+        .iter()
+        .find(|frame| frame.contains(monitor_point.0, monitor_point.1))
+        .map(|frame| frame.image.clone());
+
+    let image = screenshot::capture_region_image(monitor_point, region, placement, frozen).await?;
+
+    use tauri_plugin_clipboard_manager::ClipboardExt;
+
+    // Recognize the text and put it on the clipboard
+    let dir = ocr_model_dir(&app).await?;
+    let text = tokio::task::spawn_blocking(move || ocr::recognize_text(&dir, &image))
+        .await
+        .map_err(|e| format!("OCR task failed: {}", e))??;
+
+    if text.is_empty() {
+        notify(
+            &app,
+            "Ask Anywhere",
+            "No text was found in the selected region.",
+        );
+        return Ok(text);
+    }
+
+    app.clipboard()
+        .write_text(text.clone())
+        .map_err(|e| format!("Failed to write to clipboard: {}", e))?;
+    notify(
+        &app,
+        "Ask Anywhere",
+        &format!("Copied {} characters of text.", text.chars().count()),
+    );
+
+    Ok(text)
+}
+
+#[tauri::command]
+async fn is_ocr_ready(app: AppHandle) -> Result<bool, String> {
+    let dir = ocr_model_dir(&app).await?;
+    Ok(ocr::models_available(&dir))
+}
+
+#[tauri::command]
+async fn download_ocr_models(app: AppHandle) -> Result<(), String> {
+    let dir = ocr_model_dir(&app).await?;
+    ocr::download_models(&dir).await
+}
+
+// Folder holding the OCR models
+async fn ocr_model_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let config = load_config(app.clone()).await?;
+    if !config.ocr.model_dir.trim().is_empty() {
+        return Ok(PathBuf::from(config.ocr.model_dir.trim()));
+    }
+
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("ocr"))
+        .map_err(|e| format!("Failed to get app data folder: {}", e))
+}
+
+// How screenshots reach the requested model
+enum VisionRoute<'a> {
+    // The model can see images
+    Direct,
+    // Send the request to this vision model instead
+    Model(&'a ModelConfig),
+    // Replace the images with their recognized text
+    Ocr,
+}
+
+// Checks the requested model's vision support and applies the configured fallback
+fn vision_route<'a>(
+    config: &'a AppConfig,
+    base_url: &str,
+    model_name: &str,
+    ocr_ready: bool,
+) -> Result<VisionRoute<'a>, String> {
+    let model = config
+        .models
+        .iter()
+        .find(|m| m.base_url == base_url && m.model_name == model_name);
+    if model.is_some_and(|m| m.supports_vision) {
+        return Ok(VisionRoute::Direct);
+    }
+
+    let name = model.map(|m| m.name.as_str()).unwrap_or(model_name);
+    match config.vision_fallback.as_str() {
+        "route" if config.vision_model.is_empty() => config
+            .models
+            .iter()
+            .find(|m| m.supports_vision)
+            .map(VisionRoute::Model)
+            .ok_or_else(|| {
+                format!(
+                    "Model \"{}\" doesn't support images and no vision-capable model is configured.",
+                    name
+                )
+            }),
+        "route" => config
+            .models
+            .iter()
+            .find(|m| m.supports_vision && m.name == config.vision_model)
+            .map(VisionRoute::Model)
+            .ok_or_else(|| {
+                format!(
+                    "Model \"{}\" doesn't support images and the vision model \"{}\" isn't configured or can't see images.",
+                    name, config.vision_model
+                )
+            }),
+        "ocr" if ocr_ready => Ok(VisionRoute::Ocr),
+        "ocr" => Err(format!(
+            "Model \"{}\" doesn't support images and the OCR models aren't downloaded. Download them in the settings, select a vision-capable model or remove the screenshots.",
+            name
+        )),
+        _ => Err(format!(
+            "Model \"{}\" doesn't support images. Select a vision-capable model or remove the screenshots.",
+            name
+        )),
+    }
+}
+
+// Recognizes the text of each screenshot for models that can't see images
+async fn ocr_screenshots(app: &AppHandle, screenshots: Vec<String>) -> Result<String, String> {
+    let dir = ocr_model_dir(app).await?;
+    let texts = tokio::task::spawn_blocking(move || {
+        screenshots
+            .iter()
+            .map(|screenshot| ocr::recognize_data_url(&dir, screenshot))
+            .collect::<Result<Vec<_>, String>>()
+    })
+    .await
+    .map_err(|e| format!("OCR task failed: {}", e))??;
+
+    Ok(ocr::format_for_prompt(&texts))
+}
+
+// Appends text to the latest user message with plain string content
+fn append_to_latest_user_message(messages: &mut [serde_json::Value], text: &str) {
+    if let Some(msg) = messages
+        .iter_mut()
+        .rev()
+        .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("user"))
+    {
+        if let Some(content) = msg.get("content").and_then(|c| c.as_str()) {
+            msg["content"] = serde_json::json!(format!("{}\n\n{}", content, text));
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_frozen_frame(
+    app: AppHandle,
+    state: State<'_, FrozenFrames>,
+) -> Result<Option<String>, String> {
+    let (monitor, _) = selector_placement(&app)?;
+    let (x, y) = monitor_center(&monitor);
+
+    let image = match state
+        .0
+        .lock()
+        .await
+        .iter()
+        .find(|frame| frame.contains(x, y))
+    {
+        Some(frame) => frame.image.clone(),
+        None => return Ok(None),
+    };
+
+    // The frame is only a backdrop, so a quick lossy encoding is enough
+    let options = ScreenshotConfig {
+        max_dimension: 0,
+        format: "jpeg".to_string(),
+        quality: 90,
+        ..Default::default()
+    };
+    tokio::task::spawn_blocking(move || screenshot::encode_image(&image, &options, None))
+        .await
+        .map_err(|e| format!("Screenshot task failed: {}", e))?
+        .map(Some)
+}
 
-        // Re-register template hotkeys
-        for template in config.templates.iter() {
-            if let Some(hotkey_str) = &template.hotkey {
-                if !hotkey_str.is_empty() {
-                    let template_id = template.id.clone();
-                    let template_prompt = template.prompt.clone();
-                    let template_action = template.action.clone();
-                    let background_mode = template.background_mode;
-                    let app_clone = app.clone();
-
-                    if let Ok(template_shortcut) = hotkey_str.parse::<Shortcut>() {
-                        // Register template shortcut handler
-                        match app.global_shortcut().on_shortcut(
-                            template_shortcut.clone(),
-                            move |_app, _shortcut, event| {
-                                if event.state == tauri_plugin_global_shortcut::ShortcutState::Pressed {
-                                    let app = app_clone.clone();
-                                    let prompt = template_prompt.clone();
-                                    let action = template_action.clone();
-                                    let template_id_inner = template_id.clone();
-
-                                    tauri::async_runtime::spawn(async move {
-                                        // Capture the selected text
-                                        let captured_text = match clipboard::capture_selected_text().await {
-                                            Ok(text) => {
-                                                // Store the captured text in state
-                                                let captured_state: tauri::State<CapturedText> = app.state();
-                                                *captured_state.0.lock().await = text.clone();
-                                                text
-                                            }
-                                            Err(e) => {
-                                                eprintln!("Warning: Failed to capture selection: {}", e);
-                                                String::new()
-                                            }
-                                        };
-
-                                        if background_mode {
-                                            // Background mode: execute without showing popup
-                                            println!("Executing template {} in background mode", template_id_inner);
-
-                                            // Load config to get model info
-                                            let store = match app.store("config.json") {
-                                                Ok(s) => s,
-                                                Err(e) => {
-                                                    eprintln!("Failed to load config: {}", e);
-                                                    return;
-                                                }
-                                            };
-
-                                            let config: AppConfig = match store.get("app_config") {
-                                                Some(value) => match serde_json::from_value(value.clone()) {
-                                                    Ok(c) => c,
-                                                    Err(e) => {
-                                                        eprintln!("Failed to parse config: {}", e);
-                                                        return;
-                                                    }
-                                                },
-                                                None => {
-                                                    eprintln!("No config found");
-                                                    return;
-                                                }
-                                            };
-
-                                            let selected_model = &config.models[config.selected_model_index];
-
-                                            // Build the full prompt with captured text
-                                            let full_prompt = if !captured_text.is_empty() {
-                                                format!("{}\n\n{}", prompt, captured_text)
-                                            } else {
-                                                prompt.clone()
-                                            };
-
-                                            // Call AI API directly in background
-                                            let base_url = selected_model.base_url.clone();
-                                            let api_key = selected_model.api_key.clone();
-                                            let model_name = selected_model.model_name.clone();
-                                            let action_clone = action.clone();
-                                            let app_for_action = app.clone();
-
-                                            // Make API call
-                                            tokio::spawn(async move {
-                                                use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-
-                                                let url = if base_url.ends_with('/') {
-                                                    format!("{}chat/completions", base_url)
-                                                } else {
-                                                    format!("{}/chat/completions", base_url)
-                                                };
-
-                                                let mut headers = HeaderMap::new();
-                                                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-                                                if let Ok(auth_value) = HeaderValue::from_str(&format!("Bearer {}", api_key)) {
-                                                    headers.insert(AUTHORIZATION, auth_value);
-                                                }
-
-                                                let body = serde_json::json!({
-                                                    "model": model_name,
-                                                    "messages": [{"role": "user", "content": full_prompt}],
-                                                    "stream": false
-                                                });
-
-                                                let client = reqwest::Client::new();
-                                                match client.post(&url).headers(headers).json(&body).send().await {
-                                                    Ok(response) => {
-                                                        if response.status().is_success() {
-                                                            if let Ok(result) = response.json::<serde_json::Value>().await {
-                                                                if let Some(content) = result["choices"][0]["message"]["content"].as_str() {
-                                                                    println!("Background execution completed. Response length: {}", content.len());
-
-                                                                    // Execute action
-                                                                    if action_clone == "copy" {
-                                                                        use tauri_plugin_clipboard_manager::ClipboardExt;
-                                                                        if let Err(e) = app_for_action.clipboard().write_text(content) {
-                                                                            eprintln!("Failed to copy to clipboard: {}", e);
-                                                                        } else {
-                                                                            println!("Copied response to clipboard");
-                                                                        }
-                                                                    } else if action_clone == "replace" {
-                                                                        // Use the replace_text_in_source function
-                                                                        replace_text_in_source(app_for_action, content.to_string());
-                                                                    }
-                                                                }
-                                                            }
-                                                        } else {
-                                                            eprintln!("API error: {}", response.status());
-                                                        }
-                                                    }
-                                                    Err(e) => {
-                                                        eprintln!("Request failed: {}", e);
-                                                    }
-                                                }
-                                            });
-                                        } else {
-                                            // Normal mode: show popup and emit event
-                                            // Show the popup window with template info
-                                            if let Err(e) = show_popup_window(app.clone()).await {
-                                                eprintln!("Failed to show popup: {}", e);
-                                                return;
-                                            }
-
-                                            // Wait a bit for the window to be fully loaded
-                                            tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
-
-                                            // Emit event to trigger template execution
-                                            if let Some(popup) = app.get_webview_window("popup") {
-                                                println!("Emitting execute-template event for template: {}", template_id_inner);
-                                                if let Err(e) = popup.emit("execute-template", serde_json::json!({
-                                                    "id": template_id_inner,
-                                                    "prompt": prompt,
-                                                    "action": action,
-                                                })) {
-                                                    eprintln!("Failed to emit execute-template event: {}", e);
-                                                } else {
-                                                    println!("Successfully emitted execute-template event");
-                                                }
-                                            } else {
-                                                eprintln!("Popup window not found when trying to emit event");
-                                            }
-                                        }
-                                    });
-                                }
-                            },
-                        ) {
-                            Ok(_) => {
-                                if let Err(e) = app.global_shortcut().register(template_shortcut) {
-                                    eprintln!("Warning: Failed to register template shortcut {}: {}", hotkey_str, e);
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Warning: Failed to setup template shortcut handler {}: {}", hotkey_str, e);
-                            }
-                        }
-                    } else {
-                        eprintln!("Warning: Failed to parse template hotkey: {}", hotkey_str);
-                    }
-                }
-            }
+// Finds the selector's monitor and how its logical pixels map onto that monitor
+fn selector_placement(app: &AppHandle) -> Result<(tauri::Monitor, screenshot::Placement), String> {
+    // The region is in the selector's logical pixels, relative to its content area
+    match app.get_webview_window("screenshot-selector") {
+        Some(selector) => {
+            let monitor = selector
+                .current_monitor()
+                .map_err(|e| e.to_string())?
+                .map_or_else(|| monitor_under_cursor(app), Ok)?;
+            let position = selector.inner_position().map_err(|e| e.to_string())?;
+            let placement = screenshot::Placement {
+                scale_factor: selector.scale_factor().map_err(|e| e.to_string())?,
+                origin: (
+                    position.x - monitor.position().x,
+                    position.y - monitor.position().y,
+                ),
+            };
+            Ok((monitor, placement))
+        }
+        None => {
+            let monitor = monitor_under_cursor(app)?;
+            let placement = screenshot::Placement {
+                scale_factor: monitor.scale_factor(),
+                origin: (0, 0),
+            };
+            Ok((monitor, placement))
         }
     }
-
-    Ok(())
 }
 
-#[tauri::command]
-async fn get_captured_text(state: State<'_, CapturedText>) -> Result<String, String> {
-    let text = state.0.lock().await;
-    Ok(text.clone())
+// The monitor's center identifies it to the capture backend, in its coordinates
+fn monitor_center(monitor: &tauri::Monitor) -> (i32, i32) {
+    screenshot::capture_point(
+        monitor.position().x as f64 + monitor.size().width as f64 / 2.0,
+        monitor.position().y as f64 + monitor.size().height as f64 / 2.0,
+        monitor.scale_factor(),
+    )
 }
 
 #[tauri::command]
-async fn take_screenshot(state: State<'_, Screenshots>) -> Result<String, String> {
-    // Capture screenshot
-    let screenshot_data = screenshot::capture_screenshot().await?;
+async fn paste_clipboard_image(
+    app: AppHandle,
+    state: State<'_, Screenshots>,
+) -> Result<String, String> {
+    let image = clipboard::read_clipboard_image(&app).map_err(|e| e.to_string())?;
+
+    // Encode like a capture
+    let options = screenshot_options(&app).await;
+    let redactor = screenshot_redactor(&app).await?;
+    let screenshot_data = tokio::task::spawn_blocking(move || {
+        screenshot::encode_image(&image, &options, redactor.as_ref())
+    })
+    .await
+    .map_err(|e| format!("Screenshot task failed: {}", e))??;
 
     // Store in state
     let mut screenshots = state.0.lock().await;
@@ -413,15 +1491,23 @@ async fn take_screenshot(state: State<'_, Screenshots>) -> Result<String, String
 }
 
 #[tauri::command]
-async fn capture_screenshot_region(
+async fn list_windows() -> Result<Vec<screenshot::WindowInfo>, String> {
+    screenshot::list_windows().await
+}
+
+#[tauri::command]
+async fn capture_window(
+    app: AppHandle,
     state: State<'_, Screenshots>,
-    x: i32,
-    y: i32,
-    width: u32,
-    height: u32,
+    window_id: u32,
 ) -> Result<String, String> {
-    // Capture region
-    let screenshot_data = screenshot::capture_region(x, y, width, height).await?;
+    // Capture window
+    let options = screenshot_options(&app).await;
+    let redactor = screenshot_redactor(&app).await?;
+    let screenshot_data =
+        screenshot::capture_window_screenshot(window_id, options, redactor).await?;
+
+    save_to_history(&app, &screenshot_data).await;
 
     // Store in state
     let mut screenshots = state.0.lock().await;
@@ -443,6 +1529,55 @@ async fn clear_screenshots(state: State<'_, Screenshots>) -> Result<(), String>
     Ok(())
 }
 
+#[tauri::command]
+async fn get_screenshot_info(
+    state: State<'_, Screenshots>,
+) -> Result<Vec<screenshot::ScreenshotInfo>, String> {
+    let screenshots = state.0.lock().await.clone();
+    tokio::task::spawn_blocking(move || {
+        screenshots
+            .iter()
+            .map(|data_url| screenshot::describe_data_url(data_url))
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Failed to describe screenshots: {}", e))?
+}
+
+#[tauri::command]
+async fn list_screenshot_history(
+    app: AppHandle,
+    limit: Option<usize>,
+) -> Result<Vec<screenshot_history::SavedScreenshot>, String> {
+    let options = screenshot_options(&app).await;
+    let dir = screenshot_history_dir(&app, &options)?;
+
+    tokio::task::spawn_blocking(move || screenshot_history::list(&dir, limit.unwrap_or(50)))
+        .await
+        .map_err(|e| format!("Failed to list screenshots: {}", e))?
+}
+
+#[tauri::command]
+async fn reattach_screenshot(
+    app: AppHandle,
+    state: State<'_, Screenshots>,
+    path: String,
+) -> Result<String, String> {
+    let options = screenshot_options(&app).await;
+    let dir = screenshot_history_dir(&app, &options)?;
+
+    let screenshot_data =
+        tokio::task::spawn_blocking(move || screenshot_history::load(&dir, &PathBuf::from(path)))
+            .await
+            .map_err(|e| format!("Failed to load screenshot: {}", e))??;
+
+    // Store in state
+    let mut screenshots = state.0.lock().await;
+    screenshots.push(screenshot_data.clone());
+
+    Ok(screenshot_data)
+}
+
 #[tauri::command]
 async fn remove_screenshot(state: State<'_, Screenshots>, index: usize) -> Result<(), String> {
     let mut screenshots = state.0.lock().await;
@@ -454,24 +1589,133 @@ async fn remove_screenshot(state: State<'_, Screenshots>, index: usize) -> Resul
     }
 }
 
+#[tauri::command]
+async fn list_attachments(state: State<'_, Attachments>) -> Result<Vec<Attachment>, String> {
+    let attachments = state.0.lock().await;
+    Ok(attachments.clone())
+}
+
+#[tauri::command]
+async fn attach_file(
+    app: AppHandle,
+    screenshots_state: State<'_, Screenshots>,
+    attachments_state: State<'_, Attachments>,
+    path: String,
+) -> Result<(), String> {
+    let file_path = PathBuf::from(&path);
+    let options = screenshot_options(&app).await;
+    // Documents are still attached when images can't be redacted
+    let (redactor, redaction_error) = match screenshot_redactor(&app).await {
+        Ok(redactor) => (redactor, None),
+        Err(e) => (None, Some(e)),
+    };
+    let loaded = tokio::task::spawn_blocking(move || {
+        attachments::load_file(&file_path, &options, redactor.as_ref())
+    })
+    .await
+    .map_err(|e| format!("Failed to attach file: {}", e))??;
+
+    match loaded {
+        attachments::LoadedFile::Document(document) => {
+            let mut attachments = attachments_state.0.lock().await;
+            if attachments.iter().any(|a| a.path == document.path) {
+                return Ok(());
+            }
+
+            // Refuse files that would push the request over the limits
+            let mut pending = attachments.clone();
+            pending.push(document.clone());
+            attachments::check_limits(&pending, &screenshots_state.0.lock().await)?;
+
+            attachments.push(document);
+        }
+        attachments::LoadedFile::Image(data_url) => {
+            if let Some(e) = redaction_error {
+                return Err(e);
+            }
+
+            let attachments = attachments_state.0.lock().await;
+            let mut screenshots = screenshots_state.0.lock().await;
+
+            let mut pending = screenshots.clone();
+            pending.push(data_url.clone());
+            attachments::check_limits(&attachments, &pending)?;
+
+            screenshots.push(data_url);
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn remove_attachment(state: State<'_, Attachments>, index: usize) -> Result<(), String> {
+    let mut attachments = state.0.lock().await;
+    if index < attachments.len() {
+        attachments.remove(index);
+        Ok(())
+    } else {
+        Err("Attachment index out of bounds".to_string())
+    }
+}
+
+#[tauri::command]
+async fn clear_attachments(state: State<'_, Attachments>) -> Result<(), String> {
+    let mut attachments = state.0.lock().await;
+    attachments.clear();
+    Ok(())
+}
+
 #[tauri::command]
 async fn show_screenshot_selector(app: AppHandle) -> Result<(), String> {
+    open_screenshot_selector(app, "attach").await
+}
+
+#[tauri::command]
+async fn get_selector_mode(state: State<'_, SelectorMode>) -> Result<String, String> {
+    let mode = state.0.lock().await;
+    Ok(mode.clone())
+}
+
+// Opens the region selector on the monitor under the cursor
+async fn open_screenshot_selector(app: AppHandle, mode: &str) -> Result<(), String> {
+    let mode_state: tauri::State<SelectorMode> = app.state();
+    *mode_state.0.lock().await = mode.to_string();
+
+    // Freeze every monitor before the overlay covers menus and hover states
+    let frozen_state: tauri::State<FrozenFrames> = app.state();
+    let frames = match screenshot::capture_all_monitors().await {
+        Ok(frames) => frames,
+        Err(e) => {
+            eprintln!("Warning: Failed to freeze the screen: {}", e);
+            Vec::new()
+        }
+    };
+    *frozen_state.0.lock().await = frames;
+
+    // Cover the monitor under the cursor
+    let monitor = monitor_under_cursor(&app)?;
+
     // Check if selector window already exists
     if let Some(window) = app.get_webview_window("screenshot-selector") {
+        // Move it onto the cursor's monitor before showing it again
+        window.set_fullscreen(false).map_err(|e| e.to_string())?;
+        window
+            .set_position(*monitor.position())
+            .map_err(|e| e.to_string())?;
+        window
+            .set_size(*monitor.size())
+            .map_err(|e| e.to_string())?;
+        window.set_fullscreen(true).map_err(|e| e.to_string())?;
         window.show().map_err(|e| e.to_string())?;
         window.set_focus().map_err(|e| e.to_string())?;
+        let _ = window.emit("frozen-frame-updated", ());
         return Ok(());
     }
 
-    // Get primary monitor information
-    let monitors = app.available_monitors().map_err(|e| e.to_string())?;
-    let monitor = monitors
-        .into_iter()
-        .next()
-        .ok_or_else(|| "No monitors found".to_string())?;
-
-    let monitor_size = monitor.size();
-    let monitor_position = monitor.position();
+    // The window builder takes logical units
+    let monitor_size = monitor.size().to_logical::<f64>(monitor.scale_factor());
+    let monitor_position = monitor.position().to_logical::<f64>(monitor.scale_factor());
 
     // Create full-screen transparent overlay window
     let selector = tauri::WebviewWindowBuilder::new(
@@ -480,8 +1724,8 @@ async fn show_screenshot_selector(app: AppHandle) -> Result<(), String> {
         tauri::WebviewUrl::App("screenshot-selector.html".into()),
     )
     .title("Select Screenshot Region")
-    .inner_size(monitor_size.width as f64, monitor_size.height as f64)
-    .position(monitor_position.x as f64, monitor_position.y as f64)
+    .inner_size(monitor_size.width, monitor_size.height)
+    .position(monitor_position.x, monitor_position.y)
     .resizable(false)
     .decorations(false)
     .always_on_top(true)
@@ -492,11 +1736,119 @@ async fn show_screenshot_selector(app: AppHandle) -> Result<(), String> {
     .build()
     .map_err(|e| e.to_string())?;
 
+    // Release the frozen frames once the selection is done or cancelled
+    let frames = frozen_state.0.clone();
+    selector.on_window_event(move |event| {
+        if let tauri::WindowEvent::Destroyed = event {
+            let frames = frames.clone();
+            tauri::async_runtime::spawn(async move {
+                frames.lock().await.clear();
+            });
+        }
+    });
+
     selector.set_focus().map_err(|e| e.to_string())?;
 
     Ok(())
 }
 
+// Shows the history search window centered on the monitor under the cursor
+#[tauri::command]
+async fn show_history_search_window(app: AppHandle) -> Result<(), String> {
+    const WIDTH: f64 = 640.0;
+    const HEIGHT: f64 = 480.0;
+
+    let monitor = monitor_under_cursor(&app)?;
+    let scale_factor = monitor.scale_factor();
+    let monitor_size = monitor.size().to_logical::<f64>(scale_factor);
+    let monitor_position = monitor.position().to_logical::<f64>(scale_factor);
+    let position = tauri::LogicalPosition {
+        x: monitor_position.x + (monitor_size.width - WIDTH) / 2.0,
+        y: monitor_position.y + (monitor_size.height - HEIGHT) / 3.0,
+    };
+
+    let window = match app.get_webview_window("history-search") {
+        Some(window) => {
+            window
+                .set_position(tauri::Position::Logical(position))
+                .map_err(|e| e.to_string())?;
+            // Lets the window clear its previous search
+            let _ = window.emit("history-search-opened", ());
+            window
+        }
+        None => tauri::WebviewWindowBuilder::new(
+            &app,
+            "history-search",
+            tauri::WebviewUrl::App("history-search.html".into()),
+        )
+        .title("Search History")
+        .inner_size(WIDTH, HEIGHT)
+        .position(position.x, position.y)
+        .resizable(true)
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .transparent(true)
+        .shadow(false)
+        .visible(false)
+        .build()
+        .map_err(|e| e.to_string())?,
+    };
+
+    window.show().map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn hide_history_search_window(app: AppHandle) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window("history-search") {
+        window.hide().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// Returns the cursor position in physical desktop coordinates
+fn cursor_position(app: &AppHandle) -> (f64, f64) {
+    match app.cursor_position() {
+        Ok(position) => (position.x, position.y),
+        Err(e) => {
+            eprintln!("Warning: Failed to get cursor position: {}", e);
+            (0.0, 0.0)
+        }
+    }
+}
+
+// Returns the cursor position in the capture backend's coordinates
+fn cursor_point(app: &AppHandle) -> (i32, i32) {
+    let (x, y) = cursor_position(app);
+    let scale_factor = app
+        .monitor_from_point(x, y)
+        .ok()
+        .flatten()
+        .map_or(1.0, |monitor| monitor.scale_factor());
+    screenshot::capture_point(x, y, scale_factor)
+}
+
+// Finds the monitor containing the cursor, falling back to the primary monitor
+fn monitor_under_cursor(app: &AppHandle) -> Result<tauri::Monitor, String> {
+    let (x, y) = cursor_position(app);
+    if let Some(monitor) = app.monitor_from_point(x, y).map_err(|e| e.to_string())? {
+        return Ok(monitor);
+    }
+
+    if let Some(monitor) = app.primary_monitor().map_err(|e| e.to_string())? {
+        return Ok(monitor);
+    }
+
+    app.available_monitors()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No monitors found".to_string())
+}
+
 // Helper function to create AutoLaunch instance
 fn create_auto_launch() -> Result<AutoLaunch, String> {
     let app_name = "AskAnywhere";
@@ -549,16 +1901,52 @@ async fn toggle_autostart(app: AppHandle, enabled: bool) -> Result<(), String> {
 // Streaming AI response command
 #[tauri::command]
 async fn stream_ai_response(
+    app: AppHandle,
+    generation_state: State<'_, StreamGeneration>,
     base_url: String,
     api_key: String,
     model_name: String,
+    template: Option<String>,
+    conversation_id: Option<i64>,
     messages: Vec<serde_json::Value>,
     screenshots: Vec<String>,
+    attachments: Vec<Attachment>,
     channel: Channel<String>,
-) -> Result<(), String> {
+) -> Result<Option<i64>, String> {
     use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
     use serde_json::json;
 
+    let started_at = chrono::Utc::now().timestamp_millis();
+    let (input, captured_text) = latest_input(&messages);
+    let sent_screenshots = screenshots.clone();
+
+    // Claim a new generation, superseding any earlier stream
+    let generation = {
+        let mut current = generation_state.0.lock().await;
+        *current += 1;
+        *current
+    };
+
+    // Make sure screenshots only reach a model that can see them
+    let mut base_url = base_url;
+    let mut api_key = api_key;
+    let mut model_name = model_name;
+    let mut use_ocr = false;
+    if !screenshots.is_empty() {
+        let config = load_config(app.clone()).await?;
+        let ocr_ready = ocr::models_available(&ocr_model_dir(&app).await?);
+        match vision_route(&config, &base_url, &model_name, ocr_ready)? {
+            VisionRoute::Direct => {}
+            VisionRoute::Model(model) => {
+                println!("Routing screenshots to vision model: {}", model.name);
+                base_url = model.base_url.clone();
+                api_key = model.api_key.clone();
+                model_name = model.model_name.clone();
+            }
+            VisionRoute::Ocr => use_ocr = true,
+        }
+    }
+
     // Build the full URL
     let url = if base_url.ends_with('/') {
         format!("{}chat/completions", base_url)
@@ -574,42 +1962,52 @@ async fn stream_ai_response(
         HeaderValue::from_str(&format!("Bearer {}", api_key)).map_err(|e| e.to_string())?,
     );
 
-    // Transform messages if screenshots are provided
-    let transformed_messages = if !screenshots.is_empty() {
-        messages
-            .into_iter()
-            .map(|mut msg| {
-                // Only transform user messages
-                if msg.get("role").and_then(|r| r.as_str()) == Some("user") {
-                    if let Some(content) = msg.get("content").and_then(|c| c.as_str()) {
-                        // Build content array with text and images
-                        let mut content_parts: Vec<serde_json::Value> =
-                            vec![json!({"type": "text", "text": content})];
-
-                        // Add all screenshots as image_url parts
-                        for screenshot in &screenshots {
-                            content_parts.push(json!({
-                                "type": "image_url",
-                                "image_url": {
-                                    "url": screenshot
-                                }
-                            }));
-                        }
+    // Enforce the attachment limits before anything is sent
+    attachments::check_limits(&attachments, &screenshots)?;
 
-                        msg["content"] = json!(content_parts);
-                    }
+    // Append attached documents to the latest user message
+    let mut messages = messages;
+    if !attachments.is_empty() {
+        append_to_latest_user_message(&mut messages, &attachments::format_for_prompt(&attachments));
+    }
+
+    // Models without vision get the screenshots' text instead of the images
+    let mut screenshots = screenshots;
+    if use_ocr {
+        let text = ocr_screenshots(&app, std::mem::take(&mut screenshots)).await?;
+        append_to_latest_user_message(&mut messages, &text);
+    }
+
+    // Attach the screenshots to the latest user message only
+    if !screenshots.is_empty() {
+        if let Some(msg) = messages
+            .iter_mut()
+            .rev()
+            .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("user"))
+        {
+            if let Some(content) = msg.get("content").and_then(|c| c.as_str()) {
+                // Build content array with text and images
+                let mut content_parts: Vec<serde_json::Value> =
+                    vec![json!({"type": "text", "text": content})];
+
+                for screenshot in &screenshots {
+                    content_parts.push(json!({
+                        "type": "image_url",
+                        "image_url": {
+                            "url": screenshot
+                        }
+                    }));
                 }
-                msg
-            })
-            .collect()
-    } else {
-        messages
-    };
+
+                msg["content"] = json!(content_parts);
+            }
+        }
+    }
 
     // Build request body
     let body = json!({
         "model": model_name,
-        "messages": transformed_messages,
+        "messages": messages,
         "stream": true
     });
 
@@ -639,8 +2037,14 @@ async fn stream_ai_response(
     let mut stream = response.bytes_stream();
 
     let mut buffer = String::new();
+    let mut output = String::new();
     while let Some(chunk) = stream.next().await {
         let chunk = chunk.map_err(|e| format!("Stream error: {}", e))?;
+
+        // Stop if the stream was cancelled or superseded
+        if *generation_state.0.lock().await != generation {
+            return Err("Request cancelled".to_string());
+        }
         let chunk_str = String::from_utf8_lossy(&chunk);
 
         buffer.push_str(&chunk_str);
@@ -665,6 +2069,7 @@ async fn stream_ai_response(
                 // Parse and extract content
                 if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&json_str) {
                     if let Some(content) = parsed["choices"][0]["delta"]["content"].as_str() {
+                        output.push_str(content);
                         channel
                             .send(content.to_string())
                             .map_err(|e| e.to_string())?;
@@ -677,6 +2082,181 @@ async fn stream_ai_response(
         }
     }
 
+    let exchange = history::NewExchange {
+        conversation_id,
+        source: "popup",
+        template,
+        model: model_name,
+        input,
+        captured_text,
+        output,
+        screenshots: Vec::new(),
+        started_at,
+    };
+    Ok(record_history(&app, exchange, &sent_screenshots).await)
+}
+
+// Stores an exchange if history is enabled, returning its conversation id
+async fn record_history(
+    app: &AppHandle,
+    exchange: history::NewExchange,
+    screenshots: &[String],
+) -> Option<i64> {
+    let config = match load_config(app.clone()).await {
+        Ok(config) if config.save_history => config,
+        _ => return None,
+    };
+
+    let state: tauri::State<History> = app.state();
+    let mut history = state.0.lock().await;
+    let store = history.as_mut()?;
+
+    // Screenshots are stored as files and referenced by path, only when
+    // captures may be kept on disk at all
+    let mut exchange = exchange;
+    if config.screenshot.save_to_disk && !screenshots.is_empty() {
+        for screenshot in screenshots {
+            match store.save_screenshot(screenshot) {
+                Ok(path) => exchange.screenshots.push(path),
+                Err(e) => eprintln!("Warning: Failed to save history screenshot: {}", e),
+            }
+        }
+        if let Err(e) = store.enforce_screenshot_retention(config.screenshot.retention_count) {
+            eprintln!("Warning: {}", e);
+        }
+    }
+
+    let id = match store.record(exchange) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Warning: {}", e);
+            return None;
+        }
+    };
+
+    if let Err(e) = store.enforce_retention(config.history_retention_count) {
+        eprintln!("Warning: {}", e);
+    }
+
+    Some(id)
+}
+
+// The prompt of the latest request and the captured text sent ahead of it:
+// the last user message, and the user messages before it since the last reply
+fn latest_input(messages: &[serde_json::Value]) -> (String, String) {
+    let start = messages
+        .iter()
+        .rposition(|m| m.get("role").and_then(|r| r.as_str()) == Some("assistant"))
+        .map_or(0, |index| index + 1);
+
+    let mut inputs: Vec<&str> = messages[start..]
+        .iter()
+        .filter(|m| m.get("role").and_then(|r| r.as_str()) == Some("user"))
+        .filter_map(|m| m.get("content").and_then(|c| c.as_str()))
+        .collect();
+    let prompt = inputs.pop().unwrap_or_default().to_string();
+
+    (prompt, inputs.join("\n\n"))
+}
+
+#[tauri::command]
+async fn list_history(
+    state: State<'_, History>,
+    limit: Option<usize>,
+) -> Result<Vec<history::ConversationSummary>, String> {
+    let history = state.0.lock().await;
+    let store = history
+        .as_ref()
+        .ok_or("Conversation history is unavailable")?;
+    store.list(limit.unwrap_or(100))
+}
+
+#[tauri::command]
+async fn search_history(
+    state: State<'_, History>,
+    query: history::SearchQuery,
+    limit: Option<usize>,
+) -> Result<Vec<history::SearchHit>, String> {
+    let history = state.0.lock().await;
+    let store = history
+        .as_ref()
+        .ok_or("Conversation history is unavailable")?;
+    store.search(&query, limit.unwrap_or(50))
+}
+
+#[tauri::command]
+async fn get_conversation(
+    state: State<'_, History>,
+    id: i64,
+) -> Result<history::Conversation, String> {
+    let history = state.0.lock().await;
+    let store = history
+        .as_ref()
+        .ok_or("Conversation history is unavailable")?;
+    store.get(id)
+}
+
+// Reopens a stored conversation in the popup so follow-ups continue it
+#[tauri::command]
+async fn resume_conversation(
+    app: AppHandle,
+    state: State<'_, History>,
+    screenshots_state: State<'_, Screenshots>,
+    id: i64,
+) -> Result<(), String> {
+    let messages = {
+        let history = state.0.lock().await;
+        let store = history
+            .as_ref()
+            .ok_or("Conversation history is unavailable")?;
+        store.messages(id)?
+    };
+
+    // Earlier screenshots are attached again so the model still sees them
+    screenshots_state
+        .0
+        .lock()
+        .await
+        .extend(messages.iter().flat_map(|m| m.images.iter().cloned()));
+
+    show_popup_window(app.clone()).await?;
+
+    // Wait a bit for the window to be fully loaded
+    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+
+    let popup = app
+        .get_webview_window("popup")
+        .ok_or("Popup window not found")?;
+    popup
+        .emit(
+            "resume-conversation",
+            serde_json::json!({ "id": id, "messages": messages }),
+        )
+        .map_err(|e| format!("Failed to resume conversation: {}", e))
+}
+
+#[tauri::command]
+async fn delete_history(state: State<'_, History>, id: i64) -> Result<(), String> {
+    let mut history = state.0.lock().await;
+    let store = history
+        .as_mut()
+        .ok_or("Conversation history is unavailable")?;
+    store.delete(id)
+}
+
+#[tauri::command]
+async fn clear_history(state: State<'_, History>) -> Result<(), String> {
+    let mut history = state.0.lock().await;
+    let store = history
+        .as_mut()
+        .ok_or("Conversation history is unavailable")?;
+    store.clear()
+}
+
+#[tauri::command]
+async fn cancel_ai_response(generation_state: State<'_, StreamGeneration>) -> Result<(), String> {
+    let mut current = generation_state.0.lock().await;
+    *current += 1;
     Ok(())
 }
 
@@ -1037,30 +2617,76 @@ pub fn run() {
             }
 
             // Initialize captured text state
-            app.manage(CapturedText(Arc::new(Mutex::new(String::new()))));
+            app.manage(CapturedText(Arc::new(Mutex::new(CapturedInput::default()))));
             // Initialize popup pinned state
             app.manage(PopupPinned(Arc::new(Mutex::new(false))));
             // Initialize screenshots state
             app.manage(Screenshots(Arc::new(Mutex::new(Vec::new()))));
+            // Initialize attachments state
+            app.manage(Attachments(Arc::new(Mutex::new(Vec::new()))));
+            // Initialize selector mode state
+            app.manage(SelectorMode(Arc::new(Mutex::new("attach".to_string()))));
+            // Initialize frozen frames state
+            app.manage(FrozenFrames(Arc::new(Mutex::new(Vec::new()))));
             // Initialize app exiting state
             app.manage(AppExiting(Arc::new(Mutex::new(false))));
+            // Initialize held hotkey state
+            app.manage(HeldHotkey(Arc::new(Mutex::new(None))));
+            // Initialize stream generation state
+            app.manage(StreamGeneration(Arc::new(Mutex::new(0))));
+            // Initialize conversation history state
+            let history_store = app
+                .path()
+                .app_data_dir()
+                .map_err(|e| e.to_string())
+                .and_then(|dir| history::HistoryStore::open(&dir.join("history")));
+            if let Err(e) = &history_store {
+                eprintln!("Warning: Conversation history is unavailable: {}", e);
+            }
+            app.manage(History(Arc::new(Mutex::new(history_store.ok()))));
 
             // Load config to get autostart state
             let store = app.store("config.json")?;
             let config: AppConfig = match store.get("app_config") {
-                Some(value) => serde_json::from_value(value.clone())?,
+                Some(value) => AppConfig::from_stored(value)?,
                 None => AppConfig::default(),
             };
 
             // Setup system tray with autostart checkbox
             let settings = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
-            let autostart_item = CheckMenuItem::with_id(app, "autostart", "Autostart", true, config.autostart, None::<&str>)?;
+            let autostart_item = CheckMenuItem::with_id(
+                app,
+                "autostart",
+                "Autostart",
+                true,
+                config.autostart,
+                None::<&str>,
+            )?;
+            let pause_hotkeys_item = CheckMenuItem::with_id(
+                app,
+                "pause_hotkeys",
+                "Pause hotkeys",
+                true,
+                config.hotkeys_paused,
+                None::<&str>,
+            )?;
             let restart = MenuItem::with_id(app, "restart", "Restart", true, None::<&str>)?;
             let exit = MenuItem::with_id(app, "exit", "Exit", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&settings, &autostart_item, &restart, &exit])?;
+            let menu = Menu::with_items(
+                app,
+                &[
+                    &settings,
+                    &autostart_item,
+                    &pause_hotkeys_item,
+                    &restart,
+                    &exit,
+                ],
+            )?;
+            app.manage(PauseHotkeysItem(pause_hotkeys_item));
 
             let _tray = TrayIconBuilder::with_id("tray")
                 .icon(app.default_window_icon().unwrap().clone())
+                .tooltip(tray_tooltip(config.hotkeys_paused))
                 .menu(&menu)
                 .on_menu_event(move |app, event| match event.id.as_ref() {
                     "settings" => {
@@ -1084,6 +2710,19 @@ pub fn run() {
                             }
                         });
                     }
+                    "pause_hotkeys" => {
+                        // Toggle hotkeys pause in a new task
+                        let app_clone = app.clone();
+                        tauri::async_runtime::spawn(async move {
+                            // Load current config to get current state
+                            if let Ok(config) = load_config(app_clone.clone()).await {
+                                let new_state = !config.hotkeys_paused;
+                                if let Err(e) = set_hotkeys_paused(app_clone, new_state).await {
+                                    eprintln!("Failed to toggle hotkeys pause: {}", e);
+                                }
+                            }
+                        });
+                    }
                     "restart" => {
                         app.restart();
                     }
@@ -1124,301 +2763,13 @@ pub fn run() {
                 });
             }
 
-            // Register global shortcut
-            let app_handle = app.handle().clone();
-
+            // Register global shortcuts unless they were paused before the last exit
             #[cfg(desktop)]
             {
-                use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
-
-                // Load config to get hotkey
-                let store = app.store("config.json")?;
-                let config: AppConfig = match store.get("app_config") {
-                    Some(value) => serde_json::from_value(value.clone())?,
-                    None => AppConfig::default(),
-                };
-
-                // Parse and register hotkey
-                let shortcut_str = config.hotkeys.popup_hotkey.as_str();
-                let shortcut: Shortcut = shortcut_str
-                    .parse()
-                    .map_err(|e| format!("Failed to parse shortcut: {:?}", e))?;
-
-                // Check if already registered and unregister first
-                if app.global_shortcut().is_registered(shortcut.clone()) {
-                    eprintln!("Shortcut already registered, attempting to unregister...");
-                    let _ = app.global_shortcut().unregister(shortcut.clone());
-                }
-
-                // Register the popup shortcut handler
-                match app.global_shortcut().on_shortcut(
-                    shortcut.clone(),
-                    move |_app, _shortcut, event| {
-                        if event.state == tauri_plugin_global_shortcut::ShortcutState::Pressed {
-                            let app = app_handle.clone();
-                            tauri::async_runtime::spawn(async move {
-                                // Check if popup is already visible
-                                if let Some(popup) = app.get_webview_window("popup") {
-                                    if let Ok(is_visible) = popup.is_visible() {
-                                        if is_visible {
-                                            // Popup is already open, emit event to trigger replace
-                                            let _ = popup.emit("trigger-replace", ());
-                                            return;
-                                        }
-                                    }
-                                }
-
-                                // Popup not visible, proceed with normal flow
-                                // Capture the selected text using UI Automation API
-                                match clipboard::capture_selected_text().await {
-                                    Ok(text) => {
-                                        // Store the captured text in state
-                                        let captured_state: tauri::State<CapturedText> = app.state();
-                                        *captured_state.0.lock().await = text;
-                                    }
-                                    Err(e) => {
-                                        eprintln!("Warning: Failed to capture selection: {}", e);
-                                    }
-                                }
-
-                                // Show the popup window
-                                let _ = show_popup_window(app).await;
-                            });
-                        }
-                    },
-                ) {
-                    Ok(_) => {
-                        // Successfully registered handler, now register the shortcut
-                        if let Err(e) = app.global_shortcut().register(shortcut) {
-                            eprintln!("Warning: Failed to register global shortcut: {}. The shortcut may not work.", e);
-                            // Don't fail the app startup, just log the error
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Failed to setup shortcut handler: {}. The shortcut may not work.", e);
-                        // Don't fail the app startup, just log the error
-                    }
-                }
-
-                // Register screenshot hotkey from config
-                let screenshot_shortcut_str = config.hotkeys.screenshot_hotkey.as_str();
-                let screenshot_shortcut: Shortcut = screenshot_shortcut_str
-                    .parse()
-                    .map_err(|e| format!("Failed to parse screenshot shortcut: {:?}", e))?;
-
-                let app_for_screenshot = app.handle().clone();
-                match app.global_shortcut().on_shortcut(
-                    screenshot_shortcut.clone(),
-                    move |_app, _shortcut, event| {
-                        if event.state == tauri_plugin_global_shortcut::ShortcutState::Pressed {
-                            let app = app_for_screenshot.clone();
-                            tauri::async_runtime::spawn(async move {
-                                // Clear captured text when screenshot hotkey is triggered
-                                let captured_state: tauri::State<CapturedText> = app.state();
-                                *captured_state.0.lock().await = String::new();
-
-                                // Show screenshot selector window
-                                match show_screenshot_selector(app).await {
-                                    Ok(_) => println!("Screenshot selector opened"),
-                                    Err(e) => eprintln!("Failed to open screenshot selector: {}", e),
-                                }
-                            });
-                        }
-                    },
-                ) {
-                    Ok(_) => {
-                        if let Err(e) = app.global_shortcut().register(screenshot_shortcut) {
-                            eprintln!("Warning: Failed to register screenshot shortcut: {}", e);
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Failed to setup screenshot shortcut handler: {}", e);
-                    }
-                }
-
-                // Register template hotkeys
-                for template in config.templates.iter() {
-                    if let Some(hotkey_str) = &template.hotkey {
-                        if !hotkey_str.is_empty() {
-                            let template_id = template.id.clone();
-                            let template_prompt = template.prompt.clone();
-                            let template_action = template.action.clone();
-                            let background_mode = template.background_mode;
-                            let app_clone = app.handle().clone();
-
-                            if let Ok(template_shortcut) = hotkey_str.parse::<Shortcut>() {
-                                // Check if already registered and unregister first
-                                if app.global_shortcut().is_registered(template_shortcut.clone()) {
-                                    eprintln!("Template shortcut {} already registered, skipping...", hotkey_str);
-                                    continue;
-                                }
-
-                                // Register template shortcut handler
-                                match app.global_shortcut().on_shortcut(
-                                    template_shortcut.clone(),
-                                    move |_app, _shortcut, event| {
-                                        if event.state == tauri_plugin_global_shortcut::ShortcutState::Pressed {
-                                            let app = app_clone.clone();
-                                            let prompt = template_prompt.clone();
-                                            let action = template_action.clone();
-                                            let template_id_inner = template_id.clone();
-
-                                            tauri::async_runtime::spawn(async move {
-                                                // Capture the selected text
-                                                let captured_text = match clipboard::capture_selected_text().await {
-                                                    Ok(text) => {
-                                                        // Store the captured text in state
-                                                        let captured_state: tauri::State<CapturedText> = app.state();
-                                                        *captured_state.0.lock().await = text.clone();
-                                                        text
-                                                    }
-                                                    Err(e) => {
-                                                        eprintln!("Warning: Failed to capture selection: {}", e);
-                                                        String::new()
-                                                    }
-                                                };
-
-                                                if background_mode {
-                                                    // Background mode: execute without showing popup
-                                                    println!("Executing template {} in background mode", template_id_inner);
-
-                                                    // Load config to get model info
-                                                    let store = match app.store("config.json") {
-                                                        Ok(s) => s,
-                                                        Err(e) => {
-                                                            eprintln!("Failed to load config: {}", e);
-                                                            return;
-                                                        }
-                                                    };
-
-                                                    let config: AppConfig = match store.get("app_config") {
-                                                        Some(value) => match serde_json::from_value(value.clone()) {
-                                                            Ok(c) => c,
-                                                            Err(e) => {
-                                                                eprintln!("Failed to parse config: {}", e);
-                                                                return;
-                                                            }
-                                                        },
-                                                        None => {
-                                                            eprintln!("No config found");
-                                                            return;
-                                                        }
-                                                    };
-
-                                                    let selected_model = &config.models[config.selected_model_index];
-
-                                                    // Build the full prompt with captured text
-                                                    let full_prompt = if !captured_text.is_empty() {
-                                                        format!("{}\n\n{}", prompt, captured_text)
-                                                    } else {
-                                                        prompt.clone()
-                                                    };
-
-                                                    // Call AI API directly in background
-                                                    let base_url = selected_model.base_url.clone();
-                                                    let api_key = selected_model.api_key.clone();
-                                                    let model_name = selected_model.model_name.clone();
-                                                    let action_clone = action.clone();
-                                                    let app_for_action = app.clone();
-
-                                                    // Make API call
-                                                    tokio::spawn(async move {
-                                                        use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-
-                                                        let url = if base_url.ends_with('/') {
-                                                            format!("{}chat/completions", base_url)
-                                                        } else {
-                                                            format!("{}/chat/completions", base_url)
-                                                        };
-
-                                                        let mut headers = HeaderMap::new();
-                                                        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-                                                        if let Ok(auth_value) = HeaderValue::from_str(&format!("Bearer {}", api_key)) {
-                                                            headers.insert(AUTHORIZATION, auth_value);
-                                                        }
-
-                                                        let body = serde_json::json!({
-                                                            "model": model_name,
-                                                            "messages": [{"role": "user", "content": full_prompt}],
-                                                            "stream": false
-                                                        });
-
-                                                        let client = reqwest::Client::new();
-                                                        match client.post(&url).headers(headers).json(&body).send().await {
-                                                            Ok(response) => {
-                                                                if response.status().is_success() {
-                                                                    if let Ok(result) = response.json::<serde_json::Value>().await {
-                                                                        if let Some(content) = result["choices"][0]["message"]["content"].as_str() {
-                                                                            println!("Background execution completed. Response length: {}", content.len());
-
-                                                                            // Execute action
-                                                                            if action_clone == "copy" {
-                                                                                use tauri_plugin_clipboard_manager::ClipboardExt;
-                                                                                if let Err(e) = app_for_action.clipboard().write_text(content) {
-                                                                                    eprintln!("Failed to copy to clipboard: {}", e);
-                                                                                } else {
-                                                                                    println!("Copied response to clipboard");
-                                                                                }
-                                                                            } else if action_clone == "replace" {
-                                                                                // Use the replace_text_in_source function
-                                                                                replace_text_in_source(app_for_action, content.to_string());
-                                                                            }
-                                                                        }
-                                                                    }
-                                                                } else {
-                                                                    eprintln!("API error: {}", response.status());
-                                                                }
-                                                            }
-                                                            Err(e) => {
-                                                                eprintln!("Request failed: {}", e);
-                                                            }
-                                                        }
-                                                    });
-                                                } else {
-                                                    // Normal mode: show popup and emit event
-                                                    // Show the popup window with template info
-                                                    if let Err(e) = show_popup_window(app.clone()).await {
-                                                        eprintln!("Failed to show popup: {}", e);
-                                                        return;
-                                                    }
-
-                                                    // Wait a bit for the window to be fully loaded
-                                                    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
-
-                                                    // Emit event to trigger template execution
-                                                    if let Some(popup) = app.get_webview_window("popup") {
-                                                        println!("Emitting execute-template event for template: {}", template_id_inner);
-                                                        if let Err(e) = popup.emit("execute-template", serde_json::json!({
-                                                            "id": template_id_inner,
-                                                            "prompt": prompt,
-                                                            "action": action,
-                                                        })) {
-                                                            eprintln!("Failed to emit execute-template event: {}", e);
-                                                        } else {
-                                                            println!("Successfully emitted execute-template event");
-                                                        }
-                                                    } else {
-                                                        eprintln!("Popup window not found when trying to emit event");
-                                                    }
-                                                }
-                                            });
-                                        }
-                                    },
-                                ) {
-                                    Ok(_) => {
-                                        if let Err(e) = app.global_shortcut().register(template_shortcut) {
-                                            eprintln!("Warning: Failed to register template shortcut {}: {}", hotkey_str, e);
-                                        }
-                                    }
-                                    Err(e) => {
-                                        eprintln!("Warning: Failed to setup template shortcut handler {}: {}", hotkey_str, e);
-                                    }
-                                }
-                            } else {
-                                eprintln!("Warning: Failed to parse template hotkey: {}", hotkey_str);
-                            }
-                        }
-                    }
+                if config.hotkeys_paused {
+                    println!("Hotkeys are paused, skipping registration");
+                } else {
+                    register_hotkeys(app.handle(), &config)?;
                 }
             }
 
@@ -1430,6 +2781,7 @@ pub fn run() {
         .plugin(tauri_plugin_store::Builder::default().build())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_notification::init())
         .invoke_handler(tauri::generate_handler![
             load_config,
             save_config,
@@ -1437,41 +2789,67 @@ pub fn run() {
             import_config,
             reload_hotkeys,
             get_captured_text,
+            apply_window_context,
             show_popup_window,
             hide_popup_window,
             resize_popup_window,
             toggle_autostart,
             stream_ai_response,
+            cancel_ai_response,
+            list_history,
+            search_history,
+            show_history_search_window,
+            hide_history_search_window,
+            get_conversation,
+            resume_conversation,
+            delete_history,
+            clear_history,
             set_popup_pinned,
             is_popup_pinned,
             replace_text_in_source,
             take_screenshot,
             capture_screenshot_region,
+            get_frozen_frame,
+            get_selector_mode,
+            capture_region_text,
+            capture_scrolling_region,
+            is_ocr_ready,
+            download_ocr_models,
             get_screenshots,
             clear_screenshots,
             remove_screenshot,
+            get_screenshot_info,
+            list_screenshot_history,
+            reattach_screenshot,
+            paste_clipboard_image,
+            list_windows,
+            capture_window,
+            attach_file,
+            list_attachments,
+            remove_attachment,
+            clear_attachments,
             show_screenshot_selector,
+            set_hotkeys_paused,
+            is_hotkeys_paused,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
-        .run(|app_handle, event| {
-            match event {
-                tauri::RunEvent::ExitRequested { api, .. } => {
-                    let should_exit = match app_handle.state::<AppExiting>().0.try_lock() {
-                        Ok(guard) => *guard,
-                        Err(_) => false,
-                    };
-
-                    if !should_exit {
-                        api.prevent_exit();
-                        #[cfg(debug_assertions)]
-                        log::warn!("Exit requested but prevented (background mode)");
-                    } else {
-                        #[cfg(debug_assertions)]
-                        log::info!("App exiting normally");
-                    }
+        .run(|app_handle, event| match event {
+            tauri::RunEvent::ExitRequested { api, .. } => {
+                let should_exit = match app_handle.state::<AppExiting>().0.try_lock() {
+                    Ok(guard) => *guard,
+                    Err(_) => false,
+                };
+
+                if !should_exit {
+                    api.prevent_exit();
+                    #[cfg(debug_assertions)]
+                    log::warn!("Exit requested but prevented (background mode)");
+                } else {
+                    #[cfg(debug_assertions)]
+                    log::info!("App exiting normally");
                 }
-                _ => {}
             }
+            _ => {}
         });
 }
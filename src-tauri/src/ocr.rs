@@ -0,0 +1,289 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::RgbaImage;
+use ocrs::{ImageSource, OcrEngine, OcrEngineParams, OcrInput, TextItem};
+use rten::Model;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Model files expected in the OCR model folder
+pub const DETECTION_MODEL: &str = "text-detection.rten";
+pub const RECOGNITION_MODEL: &str = "text-recognition.rten";
+
+/// Where the ocrs project publishes its pre-trained models
+const MODEL_BASE_URL: &str = "https://ocrs-models.s3-accelerate.amazonaws.com";
+
+/// SHA-256 digests (`sha256sum`) of the published model files. Downloads are refused
+/// while a digest is empty, and discarded when they don't match.
+const DETECTION_MODEL_SHA256: &str = "";
+const RECOGNITION_MODEL_SHA256: &str = "";
+
+/// Loading the models takes a while, so the engine is kept along with its model folder
+/// and only rebuilt when a different folder is used
+static ENGINE: Mutex<Option<(PathBuf, Arc<OcrEngine>)>> = Mutex::new(None);
+
+/// A recognized character and its bounding box in image pixels
+#[derive(Debug, Clone, Copy)]
+pub struct CharBox {
+    pub char: char,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Whether both model files are present in the folder
+pub fn models_available(dir: &Path) -> bool {
+    dir.join(DETECTION_MODEL).is_file() && dir.join(RECOGNITION_MODEL).is_file()
+}
+
+/// Downloads the detection and recognition models into the folder
+pub async fn download_models(dir: &Path) -> Result<(), String> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| format!("Failed to create OCR model folder: {}", e))?;
+
+    for (name, sha256) in [
+        (DETECTION_MODEL, DETECTION_MODEL_SHA256),
+        (RECOGNITION_MODEL, RECOGNITION_MODEL_SHA256),
+    ] {
+        download_model(dir, name, sha256).await?;
+    }
+
+    Ok(())
+}
+
+/// Streams a model file to disk, keeping it only if it matches the pinned digest
+async fn download_model(dir: &Path, name: &str, expected_sha256: &str) -> Result<(), String> {
+    use sha2::{Digest, Sha256};
+    use tokio::io::AsyncWriteExt;
+
+    if expected_sha256.is_empty() {
+        return Err(format!(
+            "No checksum is pinned for {}, refusing to download it",
+            name
+        ));
+    }
+
+    let url = format!("{}/{}", MODEL_BASE_URL, name);
+    let mut response = reqwest::get(&url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to download {}: {}", name, e))?;
+
+    // Write to a temporary name first so an interrupted download isn't picked up
+    let partial = dir.join(format!("{}.part", name));
+    let mut file = tokio::fs::File::create(&partial)
+        .await
+        .map_err(|e| format!("Failed to save {}: {}", name, e))?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", name, e))?
+    {
+        hasher.update(&chunk);
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Failed to save {}: {}", name, e))?;
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to save {}: {}", name, e))?;
+    drop(file);
+
+    let sha256 = format!("{:x}", hasher.finalize());
+    if !sha256.eq_ignore_ascii_case(expected_sha256) {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(format!(
+            "Downloaded {} does not match its pinned checksum (got {})",
+            name, sha256
+        ));
+    }
+
+    tokio::fs::rename(&partial, dir.join(name))
+        .await
+        .map_err(|e| format!("Failed to save {}: {}", name, e))
+}
+
+/// Returns the OCR engine for the folder, loading its models on first use
+fn engine(dir: &Path) -> Result<Arc<OcrEngine>, String> {
+    let mut cached = ENGINE
+        .lock()
+        .map_err(|_| "OCR engine is unavailable".to_string())?;
+    if let Some((cached_dir, engine)) = cached.as_ref() {
+        if cached_dir == dir {
+            return Ok(engine.clone());
+        }
+    }
+
+    if !models_available(dir) {
+        return Err(format!(
+            "OCR models not found in {}. Download them from the settings page.",
+            dir.display()
+        ));
+    }
+
+    let detection_model = Model::load_file(dir.join(DETECTION_MODEL))
+        .map_err(|e| format!("Failed to load OCR detection model: {}", e))?;
+    let recognition_model = Model::load_file(dir.join(RECOGNITION_MODEL))
+        .map_err(|e| format!("Failed to load OCR recognition model: {}", e))?;
+
+    let engine = OcrEngine::new(OcrEngineParams {
+        detection_model: Some(detection_model),
+        recognition_model: Some(recognition_model),
+        ..Default::default()
+    })
+    .map_err(|e| format!("Failed to start OCR engine: {}", e))?;
+
+    let engine = Arc::new(engine);
+    *cached = Some((dir.to_path_buf(), engine.clone()));
+    Ok(engine)
+}
+
+/// Extracts the text of an image, one line of text per line
+pub fn recognize_text(dir: &Path, image: &RgbaImage) -> Result<String, String> {
+    let engine = engine(dir)?;
+    let input = prepare_input(&engine, image)?;
+
+    let text = engine
+        .get_text(&input)
+        .map_err(|e| format!("Failed to recognize text: {}", e))?;
+
+    Ok(text.trim().to_string())
+}
+
+/// Recognizes the text of an image line by line, keeping where each character is
+pub fn recognize_lines(dir: &Path, image: &RgbaImage) -> Result<Vec<Vec<CharBox>>, String> {
+    let engine = engine(dir)?;
+    let input = prepare_input(&engine, image)?;
+
+    let words = engine
+        .detect_words(&input)
+        .map_err(|e| format!("Failed to detect text: {}", e))?;
+    let line_rects = engine.find_text_lines(&input, &words);
+    let lines = engine
+        .recognize_text(&input, &line_rects)
+        .map_err(|e| format!("Failed to recognize text: {}", e))?;
+
+    Ok(lines
+        .into_iter()
+        .flatten()
+        .map(|line| {
+            line.chars()
+                .iter()
+                .map(|c| CharBox {
+                    char: c.char,
+                    x: c.rect.left(),
+                    y: c.rect.top(),
+                    width: c.rect.width(),
+                    height: c.rect.height(),
+                })
+                .collect()
+        })
+        .collect())
+}
+
+fn prepare_input(engine: &OcrEngine, image: &RgbaImage) -> Result<OcrInput, String> {
+    // The engine works on RGB input
+    let rgb = image::DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+    let source = ImageSource::from_bytes(rgb.as_raw(), rgb.dimensions())
+        .map_err(|e| format!("Failed to prepare image for OCR: {}", e))?;
+
+    engine
+        .prepare_input(source)
+        .map_err(|e| format!("Failed to prepare image for OCR: {}", e))
+}
+
+/// Extracts the text of an image data URL
+pub fn recognize_data_url(dir: &Path, data_url: &str) -> Result<String, String> {
+    let base64_data = data_url
+        .split_once(',')
+        .map(|(_, data)| data)
+        .ok_or_else(|| "Invalid image data URL".to_string())?;
+    let bytes = STANDARD
+        .decode(base64_data)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let image = image::load_from_memory(&bytes)
+        .map_err(|e| format!("Failed to decode image: {}", e))?
+        .to_rgba8();
+
+    recognize_text(dir, &image)
+}
+
+/// Formats recognized screenshot text as prompt context, one block per screenshot
+pub fn format_for_prompt(texts: &[String]) -> String {
+    texts
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            let text = if text.is_empty() {
+                "(no text found)"
+            } else {
+                text.as_str()
+            };
+            format!("Text in screenshot {}:\n```\n{}\n```", i + 1, text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_data_url() -> String {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        RgbaImage::new(4, 4)
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        format!(
+            "data:image/png;base64,{}",
+            STANDARD.encode(bytes.into_inner())
+        )
+    }
+
+    #[test]
+    fn formats_one_block_per_screenshot() {
+        let texts = vec!["Hello".to_string(), String::new()];
+        assert_eq!(
+            format_for_prompt(&texts),
+            "Text in screenshot 1:\n```\nHello\n```\n\nText in screenshot 2:\n```\n(no text found)\n```"
+        );
+        assert_eq!(format_for_prompt(&[]), "");
+    }
+
+    #[test]
+    fn needs_both_models() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!models_available(dir.path()));
+
+        std::fs::write(dir.path().join(DETECTION_MODEL), b"").unwrap();
+        assert!(!models_available(dir.path()));
+
+        std::fs::write(dir.path().join(RECOGNITION_MODEL), b"").unwrap();
+        assert!(models_available(dir.path()));
+    }
+
+    #[test]
+    fn rejects_invalid_data_urls() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = recognize_data_url(dir.path(), "not a data url").unwrap_err();
+        assert_eq!(err, "Invalid image data URL");
+
+        let err = recognize_data_url(dir.path(), "data:image/png;base64,***").unwrap_err();
+        assert!(err.starts_with("Failed to decode image"), "{}", err);
+
+        let not_an_image = format!("data:image/png;base64,{}", STANDARD.encode(b"text"));
+        let err = recognize_data_url(dir.path(), &not_an_image).unwrap_err();
+        assert!(err.starts_with("Failed to decode image"), "{}", err);
+    }
+
+    #[test]
+    fn reports_missing_models() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = recognize_data_url(dir.path(), &png_data_url()).unwrap_err();
+        assert!(err.starts_with("OCR models not found"), "{}", err);
+    }
+}
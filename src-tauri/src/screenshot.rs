@@ -1,92 +1,691 @@
+use crate::annotation::{self, Annotation};
+use crate::config::ScreenshotConfig;
+use crate::redaction::Redactor;
 use base64::{engine::general_purpose::STANDARD, Engine};
-use image::ImageFormat;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, RgbaImage};
+use serde::Serialize;
 use std::io::Cursor;
-use xcap::Monitor;
+use xcap::{Monitor, Window};
 
-/// Captures a screenshot of the primary monitor and returns it as a base64-encoded data URL
-pub async fn capture_screenshot() -> Result<String, String> {
-    capture_full_screen().await
+/// Longest edge of the window thumbnails returned by `list_windows`
+const THUMBNAIL_SIZE: u32 = 320;
+
+/// Whether xcap measures monitors and windows in logical points rather than physical pixels
+const BACKEND_USES_POINTS: bool = cfg!(target_os = "macos");
+
+/// A selected region in logical (CSS) pixels, relative to the selector window
+#[derive(Debug, Clone, Copy)]
+pub struct Region {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
 }
 
-/// Captures the entire primary monitor screen
-async fn capture_full_screen() -> Result<String, String> {
-    // Spawn blocking task for screenshot capture
-    let screenshot_data = tokio::task::spawn_blocking(|| -> Result<Vec<u8>, String> {
-        // Get all monitors
-        let monitors = Monitor::all().map_err(|e| format!("Failed to get monitors: {}", e))?;
+/// A capturable application window
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowInfo {
+    pub id: u32,
+    pub title: String,
+    pub app_name: String,
+    /// PNG data URL of a small preview, if the window could be captured
+    pub thumbnail: Option<String>,
+}
 
-        // Use the primary monitor (or first available)
-        let monitor = monitors
-            .into_iter()
-            .next()
-            .ok_or_else(|| "No monitors found".to_string())?;
+/// Size and estimated cost of an encoded screenshot
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreenshotInfo {
+    /// Length of the data URL sent to the provider
+    pub bytes: usize,
+    pub width: u32,
+    pub height: u32,
+    /// Approximate vision token cost
+    pub tokens: u32,
+}
+
+/// A monitor image captured when the screenshot selector opened
+pub struct Frame {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub image: RgbaImage,
+}
+
+impl Frame {
+    /// Whether the frame's monitor contains a point in capture coordinates (see `capture_point`)
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && x < self.x + self.width as i32
+            && y < self.y + self.height as i32
+    }
+}
+
+/// Where the selector sits on its monitor
+#[derive(Debug, Clone, Copy)]
+pub struct Placement {
+    /// Device pixels per logical pixel of the selector window
+    pub scale_factor: f64,
+    /// Physical offset of the selector's content from the monitor's top-left corner
+    pub origin: (i32, i32),
+}
+
+/// Converts a physical desktop point, as Tauri reports it, into the coordinates xcap
+/// uses for monitors and windows, given the scale factor of the monitor under the point
+pub fn capture_point(x: f64, y: f64, scale_factor: f64) -> (i32, i32) {
+    let scale = if BACKEND_USES_POINTS {
+        scale_factor
+    } else {
+        1.0
+    };
+    to_logical(x, y, scale)
+}
+
+fn to_logical(x: f64, y: f64, scale_factor: f64) -> (i32, i32) {
+    (
+        (x / scale_factor).floor() as i32,
+        (y / scale_factor).floor() as i32,
+    )
+}
+
+/// Captures a screenshot of the monitor containing the given point
+/// and returns it as a base64-encoded data URL
+pub async fn capture_screenshot(
+    x: i32,
+    y: i32,
+    options: ScreenshotConfig,
+    redactor: Option<Redactor>,
+) -> Result<String, String> {
+    capture_full_screen(x, y, options, redactor).await
+}
+
+/// Finds the monitor containing a point in capture coordinates,
+/// falling back to the primary monitor (or first available)
+fn monitor_at(x: i32, y: i32) -> Result<Monitor, String> {
+    if let Ok(monitor) = Monitor::from_point(x, y) {
+        return Ok(monitor);
+    }
+
+    let monitors = Monitor::all().map_err(|e| format!("Failed to get monitors: {}", e))?;
+    let primary = monitors.iter().position(|m| m.is_primary()).unwrap_or(0);
+    monitors
+        .into_iter()
+        .nth(primary)
+        .ok_or_else(|| "No monitors found".to_string())
+}
+
+/// Captures the entire screen of the monitor containing the given point
+async fn capture_full_screen(
+    x: i32,
+    y: i32,
+    options: ScreenshotConfig,
+    redactor: Option<Redactor>,
+) -> Result<String, String> {
+    // Spawn blocking task for screenshot capture
+    tokio::task::spawn_blocking(move || -> Result<String, String> {
+        let monitor = monitor_at(x, y)?;
 
         // Capture the screenshot
         let image = monitor
             .capture_image()
             .map_err(|e| format!("Failed to capture screenshot: {}", e))?;
 
-        // Convert to PNG format in memory
-        let mut buffer = Cursor::new(Vec::new());
-        image
-            .write_to(&mut buffer, ImageFormat::Png)
-            .map_err(|e| format!("Failed to encode image: {}", e))?;
+        encode_image(&image, &options, redactor.as_ref())
+    })
+    .await
+    .map_err(|e| format!("Screenshot task failed: {}", e))?
+}
+
+/// Captures every monitor at once, so a selection can be made on a frozen screen
+pub async fn capture_all_monitors() -> Result<Vec<Frame>, String> {
+    tokio::task::spawn_blocking(|| -> Result<Vec<Frame>, String> {
+        let monitors = Monitor::all().map_err(|e| format!("Failed to get monitors: {}", e))?;
 
-        Ok(buffer.into_inner())
+        monitors
+            .iter()
+            .map(|monitor| {
+                let image = monitor
+                    .capture_image()
+                    .map_err(|e| format!("Failed to capture screenshot: {}", e))?;
+
+                Ok(Frame {
+                    x: monitor.x(),
+                    y: monitor.y(),
+                    width: monitor.width(),
+                    height: monitor.height(),
+                    image,
+                })
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Screenshot task failed: {}", e))?
+}
+
+/// Captures a region of the monitor containing `monitor_point`.
+/// The region is in logical pixels and is mapped onto the monitor image using `placement`.
+/// A `frozen` monitor image is cropped instead of capturing the screen again.
+/// Annotations are drawn onto the crop before it is downscaled and encoded.
+pub async fn capture_region(
+    monitor_point: (i32, i32),
+    region: Region,
+    placement: Placement,
+    options: ScreenshotConfig,
+    redactor: Option<Redactor>,
+    frozen: Option<RgbaImage>,
+    annotations: Vec<Annotation>,
+) -> Result<String, String> {
+    let mut cropped = capture_region_image(monitor_point, region, placement, frozen).await?;
+
+    tokio::task::spawn_blocking(move || {
+        annotation::apply(&mut cropped, &annotations, placement.scale_factor);
+        encode_image(&cropped, &options, redactor.as_ref())
     })
     .await
-    .map_err(|e| format!("Screenshot task failed: {}", e))??;
+    .map_err(|e| format!("Screenshot task failed: {}", e))?
+}
+
+/// Captures a region like `capture_region`, returning the image without encoding it
+pub async fn capture_region_image(
+    monitor_point: (i32, i32),
+    region: Region,
+    placement: Placement,
+    frozen: Option<RgbaImage>,
+) -> Result<RgbaImage, String> {
+    tokio::task::spawn_blocking(move || -> Result<RgbaImage, String> {
+        let full_image = match frozen {
+            Some(image) => image,
+            None => {
+                // Capture the full screen first
+                monitor_at(monitor_point.0, monitor_point.1)?
+                    .capture_image()
+                    .map_err(|e| format!("Failed to capture screenshot: {}", e))?
+            }
+        };
+
+        // Crop the image to the selected region
+        crop_region(&full_image, region, placement)
+    })
+    .await
+    .map_err(|e| format!("Screenshot task failed: {}", e))?
+}
+
+/// Crops a logical-pixel region out of a physical-pixel monitor image.
+/// The region is scaled, offset by the selector origin and clamped to the image bounds.
+pub fn crop_region(
+    image: &RgbaImage,
+    region: Region,
+    placement: Placement,
+) -> Result<RgbaImage, String> {
+    if placement.scale_factor.is_nan() || placement.scale_factor <= 0.0 {
+        return Err(format!("Invalid scale factor {}", placement.scale_factor));
+    }
+
+    // Normalize selections dragged up or to the left
+    let left = region.x.min(region.x + region.width);
+    let right = region.x.max(region.x + region.width);
+    let top = region.y.min(region.y + region.height);
+    let bottom = region.y.max(region.y + region.height);
+
+    // Round outwards so the crop never loses a partially selected pixel
+    let to_physical = |value: f64, offset: i32| value * placement.scale_factor + offset as f64;
+    let left = to_physical(left, placement.origin.0).floor();
+    let top = to_physical(top, placement.origin.1).floor();
+    let right = to_physical(right, placement.origin.0).ceil();
+    let bottom = to_physical(bottom, placement.origin.1).ceil();
+
+    // Clamp to the image so off-screen coordinates can't wrap around
+    let clamp = |value: f64, max: u32| value.clamp(0.0, max as f64) as u32;
+    let left = clamp(left, image.width());
+    let top = clamp(top, image.height());
+    let right = clamp(right, image.width());
+    let bottom = clamp(bottom, image.height());
+
+    if right <= left || bottom <= top {
+        return Err("The selected region is empty or outside the screen".to_string());
+    }
+
+    Ok(image::imageops::crop_imm(image, left, top, right - left, bottom - top).to_image())
+}
+
+/// Downscales an image to the configured size and encodes it in the configured format.
+/// Sensitive text is pixelated first when a redactor is given.
+pub fn encode_image(
+    image: &RgbaImage,
+    options: &ScreenshotConfig,
+    redactor: Option<&Redactor>,
+) -> Result<String, String> {
+    let redacted;
+    let image = match redactor {
+        Some(redactor) => {
+            let mut copy = image.clone();
+            let hidden = redactor.apply(&mut copy)?;
+            if hidden > 0 {
+                println!("Redacted {} sensitive text regions", hidden);
+            }
+            redacted = copy;
+            &redacted
+        }
+        None => image,
+    };
+
+    let (width, height) = fit_within(image.width(), image.height(), options.max_dimension);
+    let resized;
+    let image = if (width, height) != image.dimensions() {
+        resized = image::imageops::resize(image, width, height, FilterType::Triangle);
+        &resized
+    } else {
+        image
+    };
+
+    let mut buffer = Cursor::new(Vec::new());
+    let mime = match options.format.as_str() {
+        "jpeg" => {
+            // JPEG has no alpha channel
+            let rgb = DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+            let encoder = JpegEncoder::new_with_quality(&mut buffer, options.quality.clamp(1, 100));
+            rgb.write_with_encoder(encoder)
+                .map_err(|e| format!("Failed to encode image: {}", e))?;
+            "image/jpeg"
+        }
+        "webp" => {
+            // The image crate only encodes lossless WebP, so quality doesn't apply
+            image
+                .write_with_encoder(WebPEncoder::new_lossless(&mut buffer))
+                .map_err(|e| format!("Failed to encode image: {}", e))?;
+            "image/webp"
+        }
+        _ => return image_to_data_url(image),
+    };
+
+    Ok(format!(
+        "data:{};base64,{}",
+        mime,
+        STANDARD.encode(buffer.into_inner())
+    ))
+}
+
+/// Scales dimensions down so the longest edge is at most `max_dimension` (0 means no limit)
+fn fit_within(width: u32, height: u32, max_dimension: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if max_dimension == 0 || longest <= max_dimension {
+        return (width, height);
+    }
+
+    let scale = max_dimension as f64 / longest as f64;
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// Reads the payload size and dimensions of an image data URL
+pub fn describe_data_url(data_url: &str) -> Result<ScreenshotInfo, String> {
+    let base64_data = data_url
+        .split_once(',')
+        .map(|(_, data)| data)
+        .ok_or_else(|| "Invalid image data URL".to_string())?;
+    let bytes = STANDARD
+        .decode(base64_data)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+
+    // Only the header is parsed to get the dimensions
+    let (width, height) = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image: {}", e))?
+        .into_dimensions()
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+
+    Ok(ScreenshotInfo {
+        bytes: data_url.len(),
+        width,
+        height,
+        tokens: estimate_image_tokens(width, height),
+    })
+}
+
+/// Estimates the vision token cost of an image using OpenAI's high-detail tiling:
+/// fit within 2048x2048, scale the short side to 768, then 170 tokens per 512px tile plus 85
+pub fn estimate_image_tokens(width: u32, height: u32) -> u32 {
+    let (width, height) = fit_within(width, height, 2048);
+    let shortest = width.min(height);
+    let (width, height) = if shortest > 768 {
+        let scale = 768.0 / shortest as f64;
+        (
+            (width as f64 * scale).round() as u32,
+            (height as f64 * scale).round() as u32,
+        )
+    } else {
+        (width, height)
+    };
+
+    let tiles = width.div_ceil(512) * height.div_ceil(512);
+    85 + 170 * tiles
+}
+
+/// Encodes an image as a base64 PNG data URL
+pub fn image_to_data_url(image: &RgbaImage) -> Result<String, String> {
+    // Convert to PNG format in memory
+    let mut buffer = Cursor::new(Vec::new());
+    image
+        .write_to(&mut buffer, ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
 
     // Encode to base64
-    let base64_data = STANDARD.encode(&screenshot_data);
+    let base64_data = STANDARD.encode(buffer.into_inner());
 
     // Return as data URL
     Ok(format!("data:image/png;base64,{}", base64_data))
 }
 
-/// Captures a region of the screen given coordinates and dimensions
-pub async fn capture_region(x: i32, y: i32, width: u32, height: u32) -> Result<String, String> {
-    let screenshot_data = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, String> {
-        // Get all monitors
-        let monitors = Monitor::all().map_err(|e| format!("Failed to get monitors: {}", e))?;
+/// Lists visible windows in front-to-back order
+fn visible_windows() -> Result<Vec<Window>, String> {
+    let windows = Window::all().map_err(|e| format!("Failed to get windows: {}", e))?;
+
+    Ok(windows
+        .into_iter()
+        .filter(|w| !w.is_minimized() && w.width() > 0 && w.height() > 0)
+        .collect())
+}
 
-        // Use the primary monitor
-        let monitor = monitors
+/// Lists the windows that can be captured, with a thumbnail of each
+pub async fn list_windows() -> Result<Vec<WindowInfo>, String> {
+    tokio::task::spawn_blocking(|| -> Result<Vec<WindowInfo>, String> {
+        let windows = visible_windows()?
             .into_iter()
-            .next()
-            .ok_or_else(|| "No monitors found".to_string())?;
+            .filter(|w| !w.title().is_empty())
+            .map(|w| {
+                // A window that can't be captured is still listed, just without a preview
+                let thumbnail = w.capture_image().ok().and_then(|image| {
+                    let (width, height) = fit_within(image.width(), image.height(), THUMBNAIL_SIZE);
+                    let thumbnail = image::imageops::thumbnail(&image, width, height);
+                    image_to_data_url(&thumbnail).ok()
+                });
 
-        // Capture the full screen first
-        let full_image = monitor
-            .capture_image()
-            .map_err(|e| format!("Failed to capture screenshot: {}", e))?;
+                WindowInfo {
+                    id: w.id(),
+                    title: w.title().to_string(),
+                    app_name: w.app_name().to_string(),
+                    thumbnail,
+                }
+            })
+            .collect();
 
-        // Crop the image to the selected region
-        let cropped = image::imageops::crop_imm(&full_image, x as u32, y as u32, width, height);
+        Ok(windows)
+    })
+    .await
+    .map_err(|e| format!("Screenshot task failed: {}", e))?
+}
 
-        // Convert to PNG
-        let mut buffer = Cursor::new(Vec::new());
-        cropped
-            .to_image()
-            .write_to(&mut buffer, ImageFormat::Png)
-            .map_err(|e| format!("Failed to encode image: {}", e))?;
+/// Captures a screenshot of a specific window
+pub async fn capture_window_screenshot(
+    window_id: u32,
+    options: ScreenshotConfig,
+    redactor: Option<Redactor>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || -> Result<String, String> {
+        let window = visible_windows()?
+            .into_iter()
+            .find(|w| w.id() == window_id)
+            .ok_or_else(|| format!("Window {} not found", window_id))?;
 
-        Ok(buffer.into_inner())
+        let image = window
+            .capture_image()
+            .map_err(|e| format!("Failed to capture window: {}", e))?;
+
+        encode_image(&image, &options, redactor.as_ref())
     })
     .await
-    .map_err(|e| format!("Screenshot task failed: {}", e))??;
+    .map_err(|e| format!("Screenshot task failed: {}", e))?
+}
 
-    // Encode to base64
-    let base64_data = STANDARD.encode(&screenshot_data);
+/// Captures the topmost window containing the given point in desktop coordinates
+pub async fn capture_window_at(
+    x: i32,
+    y: i32,
+    options: ScreenshotConfig,
+    redactor: Option<Redactor>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || -> Result<String, String> {
+        let window = visible_windows()?
+            .into_iter()
+            .find(|w| {
+                x >= w.x()
+                    && y >= w.y()
+                    && x < w.x() + w.width() as i32
+                    && y < w.y() + w.height() as i32
+            })
+            .ok_or_else(|| "No window found under the cursor".to_string())?;
 
-    // Return as data URL
-    Ok(format!("data:image/png;base64,{}", base64_data))
+        let image = window
+            .capture_image()
+            .map_err(|e| format!("Failed to capture window: {}", e))?;
+
+        encode_image(&image, &options, redactor.as_ref())
+    })
+    .await
+    .map_err(|e| format!("Screenshot task failed: {}", e))?
 }
 
-/// Captures a screenshot of a specific window (future enhancement)
-#[allow(dead_code)]
-pub async fn capture_window_screenshot(_window_id: u32) -> Result<String, String> {
-    // TODO: Implement window-specific screenshot
-    // This would use xcap::Window::all() and filter by window ID
-    Err("Window screenshot not yet implemented".to_string())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn fits_long_edges_within_the_limit() {
+        assert_eq!(fit_within(3840, 2160, 1920), (1920, 1080));
+        assert_eq!(fit_within(1080, 1920, 960), (540, 960));
+        assert_eq!(fit_within(800, 600, 1920), (800, 600));
+        assert_eq!(fit_within(3840, 2160, 0), (3840, 2160));
+    }
+
+    #[test]
+    fn estimates_tile_based_token_cost() {
+        assert_eq!(estimate_image_tokens(512, 512), 255);
+        assert_eq!(estimate_image_tokens(1024, 1024), 765);
+        assert_eq!(estimate_image_tokens(3840, 2160), 1105);
+    }
+
+    #[test]
+    fn encodes_downscaled_images_in_the_configured_format() {
+        let image = coordinate_image(200, 100);
+        for (format, mime) in [
+            ("png", "data:image/png;base64,"),
+            ("jpeg", "data:image/jpeg;base64,"),
+            ("webp", "data:image/webp;base64,"),
+        ] {
+            let options = ScreenshotConfig {
+                max_dimension: 50,
+                format: format.to_string(),
+                quality: 80,
+                ..Default::default()
+            };
+            let data_url = encode_image(&image, &options, None).unwrap();
+            assert!(data_url.starts_with(mime), "{}", format);
+
+            let info = describe_data_url(&data_url).unwrap();
+            assert_eq!((info.width, info.height), (50, 25), "{}", format);
+            assert_eq!(info.bytes, data_url.len());
+        }
+    }
+
+    #[test]
+    fn finds_scaled_monitors_from_physical_points() {
+        // A 2x monitor right of a 1440 point wide one, as xcap reports it in points
+        let frame = Frame {
+            x: 1440,
+            y: 0,
+            width: 1512,
+            height: 982,
+            image: RgbaImage::new(1, 1),
+        };
+
+        // Tauri puts its center at 2880 + 3024 / 2 by 0 + 1964 / 2 physical pixels
+        let (x, y) = to_logical(4392.0, 982.0, 2.0);
+        assert!(frame.contains(x, y));
+        assert!(!frame.contains(4392, 982));
+
+        assert_eq!(to_logical(2879.0, 1963.0, 2.0), (1439, 981));
+        assert_eq!(to_logical(-3.0, 7.5, 1.5), (-2, 5));
+        assert_eq!(capture_point(4392.0, 982.0, 1.0), (4392, 982));
+    }
+
+    /// Builds an image where every pixel encodes its own coordinates
+    fn coordinate_image(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8, y as u8, 0, 255]))
+    }
+
+    fn region(x: f64, y: f64, width: f64, height: f64) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn placement(scale_factor: f64, origin: (i32, i32)) -> Placement {
+        Placement {
+            scale_factor,
+            origin,
+        }
+    }
+
+    #[test]
+    fn crops_at_unit_scale() {
+        let image = coordinate_image(100, 80);
+        let cropped = crop_region(
+            &image,
+            region(10.0, 20.0, 30.0, 15.0),
+            placement(1.0, (0, 0)),
+        )
+        .unwrap();
+
+        assert_eq!(cropped.dimensions(), (30, 15));
+        assert_eq!(cropped.get_pixel(0, 0), &Rgba([10, 20, 0, 255]));
+        assert_eq!(cropped.get_pixel(29, 14), &Rgba([39, 34, 0, 255]));
+    }
+
+    #[test]
+    fn scales_logical_coordinates_to_physical_pixels() {
+        let image = coordinate_image(150, 150);
+        let cropped = crop_region(
+            &image,
+            region(10.0, 20.0, 40.0, 30.0),
+            placement(1.5, (0, 0)),
+        )
+        .unwrap();
+
+        assert_eq!(cropped.dimensions(), (60, 45));
+        assert_eq!(cropped.get_pixel(0, 0), &Rgba([15, 30, 0, 255]));
+    }
+
+    #[test]
+    fn rounds_fractional_edges_outwards() {
+        let image = coordinate_image(100, 100);
+        let cropped =
+            crop_region(&image, region(1.0, 1.0, 3.0, 3.0), placement(1.25, (0, 0))).unwrap();
+
+        // 1.25..5.0 becomes 1..5
+        assert_eq!(cropped.dimensions(), (4, 4));
+        assert_eq!(cropped.get_pixel(0, 0), &Rgba([1, 1, 0, 255]));
+    }
+
+    #[test]
+    fn applies_the_selector_origin() {
+        let image = coordinate_image(100, 100);
+        let cropped = crop_region(
+            &image,
+            region(0.0, 0.0, 10.0, 10.0),
+            placement(2.0, (5, 25)),
+        )
+        .unwrap();
+
+        assert_eq!(cropped.dimensions(), (20, 20));
+        assert_eq!(cropped.get_pixel(0, 0), &Rgba([5, 25, 0, 255]));
+    }
+
+    #[test]
+    fn clamps_negative_coordinates() {
+        let image = coordinate_image(100, 100);
+        let cropped = crop_region(
+            &image,
+            region(-10.0, -5.0, 20.0, 15.0),
+            placement(1.0, (0, 0)),
+        )
+        .unwrap();
+
+        assert_eq!(cropped.dimensions(), (10, 10));
+        assert_eq!(cropped.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn clamps_regions_past_the_edge() {
+        let image = coordinate_image(100, 50);
+        let cropped = crop_region(
+            &image,
+            region(90.0, 40.0, 50.0, 50.0),
+            placement(1.0, (0, 0)),
+        )
+        .unwrap();
+
+        assert_eq!(cropped.dimensions(), (10, 10));
+        assert_eq!(cropped.get_pixel(9, 9), &Rgba([99, 49, 0, 255]));
+    }
+
+    #[test]
+    fn normalizes_negative_sizes() {
+        let image = coordinate_image(100, 100);
+        let cropped = crop_region(
+            &image,
+            region(40.0, 40.0, -20.0, -10.0),
+            placement(1.0, (0, 0)),
+        )
+        .unwrap();
+
+        assert_eq!(cropped.dimensions(), (20, 10));
+        assert_eq!(cropped.get_pixel(0, 0), &Rgba([20, 30, 0, 255]));
+    }
+
+    #[test]
+    fn rejects_empty_regions() {
+        let image = coordinate_image(100, 100);
+
+        assert!(crop_region(
+            &image,
+            region(10.0, 10.0, 0.0, 20.0),
+            placement(1.0, (0, 0))
+        )
+        .is_err());
+        assert!(crop_region(
+            &image,
+            region(200.0, 10.0, 20.0, 20.0),
+            placement(1.0, (0, 0))
+        )
+        .is_err());
+        assert!(crop_region(
+            &image,
+            region(-50.0, 10.0, 20.0, 20.0),
+            placement(1.0, (0, 0))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_scale_factors() {
+        let image = coordinate_image(10, 10);
+
+        assert!(crop_region(&image, region(0.0, 0.0, 5.0, 5.0), placement(0.0, (0, 0))).is_err());
+        assert!(crop_region(
+            &image,
+            region(0.0, 0.0, 5.0, 5.0),
+            placement(f64::NAN, (0, 0))
+        )
+        .is_err());
+    }
 }
@@ -0,0 +1,186 @@
+use crate::screenshot;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Saved screenshots are named `screenshot-<timestamp>.<ext>`
+const FILE_PREFIX: &str = "screenshot-";
+
+/// Longest edge of the history thumbnails
+const THUMBNAIL_SIZE: u32 = 160;
+
+/// A screenshot saved to the history folder
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedScreenshot {
+    pub name: String,
+    pub path: String,
+    /// Milliseconds since the Unix epoch
+    pub created_at: i64,
+    pub size: u64,
+    /// JPEG data URL of a small preview
+    pub thumbnail: Option<String>,
+}
+
+/// Writes a screenshot data URL to the folder with a timestamped name
+pub fn save(dir: &Path, data_url: &str) -> Result<PathBuf, String> {
+    let (mime, bytes) = decode_data_url(data_url)?;
+    let extension = match mime.as_str() {
+        "image/jpeg" => "jpg",
+        "image/webp" => "webp",
+        _ => "png",
+    };
+
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create screenshot folder: {}", e))?;
+
+    // Several screenshots can be saved within the same millisecond
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S-%3f");
+    let mut path = dir.join(format!("{}{}.{}", FILE_PREFIX, timestamp, extension));
+    let mut counter = 1;
+    while path.exists() {
+        path = dir.join(format!(
+            "{}{}-{}.{}",
+            FILE_PREFIX, timestamp, counter, extension
+        ));
+        counter += 1;
+    }
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to save screenshot: {}", e))?;
+
+    Ok(path)
+}
+
+/// Deletes the oldest screenshots beyond `keep` (0 keeps everything)
+pub fn enforce_retention(dir: &Path, keep: usize) -> Result<(), String> {
+    if keep == 0 {
+        return Ok(());
+    }
+
+    let files = screenshot_files(dir)?;
+    if files.len() <= keep {
+        return Ok(());
+    }
+
+    for path in &files[keep..] {
+        if let Err(e) = std::fs::remove_file(path) {
+            eprintln!("Warning: Failed to delete old screenshot: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists saved screenshots, newest first
+pub fn list(dir: &Path, limit: usize) -> Result<Vec<SavedScreenshot>, String> {
+    let screenshots = screenshot_files(dir)?
+        .into_iter()
+        .take(limit)
+        .map(|path| {
+            let metadata = std::fs::metadata(&path).ok();
+            let created_at = metadata
+                .as_ref()
+                .and_then(|m| m.modified().ok())
+                .map(|time| chrono::DateTime::<chrono::Utc>::from(time).timestamp_millis())
+                .unwrap_or_default();
+
+            SavedScreenshot {
+                name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path: path.to_string_lossy().to_string(),
+                created_at,
+                size: metadata.map(|m| m.len()).unwrap_or_default(),
+                thumbnail: thumbnail(&path),
+            }
+        })
+        .collect();
+
+    Ok(screenshots)
+}
+
+/// Reads a saved screenshot back as a data URL, keeping its original encoding.
+/// Only files inside the history folder can be loaded.
+pub fn load(dir: &Path, path: &Path) -> Result<String, String> {
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to open screenshot folder: {}", e))?;
+    let path = path
+        .canonicalize()
+        .map_err(|e| format!("Failed to open screenshot: {}", e))?;
+    if !path.starts_with(&dir) || !is_screenshot_file(&path) {
+        return Err("Not a saved screenshot".to_string());
+    }
+
+    let mime = match extension(&path).as_str() {
+        "jpg" => "image/jpeg",
+        "webp" => "image/webp",
+        _ => "image/png",
+    };
+    let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read screenshot: {}", e))?;
+
+    Ok(format!("data:{};base64,{}", mime, STANDARD.encode(bytes)))
+}
+
+/// Saved screenshot files in the folder, newest first
+fn screenshot_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // Nothing has been saved yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read screenshot folder: {}", e)),
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && is_screenshot_file(path))
+        .collect();
+
+    // Timestamped names sort chronologically
+    files.sort();
+    files.reverse();
+
+    Ok(files)
+}
+
+fn is_screenshot_file(path: &Path) -> bool {
+    let named = path
+        .file_name()
+        .map(|n| n.to_string_lossy().starts_with(FILE_PREFIX))
+        .unwrap_or(false);
+
+    named && matches!(extension(path).as_str(), "png" | "jpg" | "webp")
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn thumbnail(path: &Path) -> Option<String> {
+    let image = image::open(path).ok()?;
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgba8();
+    let options = crate::config::ScreenshotConfig {
+        max_dimension: 0,
+        format: "jpeg".to_string(),
+        quality: 80,
+        ..Default::default()
+    };
+
+    screenshot::encode_image(&thumbnail, &options, None).ok()
+}
+
+fn decode_data_url(data_url: &str) -> Result<(String, Vec<u8>), String> {
+    let (header, data) = data_url
+        .split_once(',')
+        .ok_or_else(|| "Invalid image data URL".to_string())?;
+    let mime = header
+        .trim_start_matches("data:")
+        .trim_end_matches(";base64")
+        .to_string();
+    let bytes = STANDARD
+        .decode(data)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+
+    Ok((mime, bytes))
+}
@@ -0,0 +1,224 @@
+use crate::screenshot::{self, Placement, Region};
+use enigo::{Axis, Enigo, Mouse, Settings};
+use image::{imageops, RgbaImage};
+use std::time::Duration;
+
+/// Stop after this many frames even if the content keeps moving
+const MAX_FRAMES: usize = 30;
+
+/// Stop once the stitched image is this tall
+const MAX_HEIGHT: u32 = 20_000;
+
+/// Mouse wheel clicks between frames
+const SCROLL_CLICKS: i32 = 3;
+
+/// Time for smooth scrolling and lazy content to settle before the next frame
+const SETTLE_DELAY: Duration = Duration::from_millis(400);
+
+/// Columns sampled from each row when matching frames
+const ROW_SAMPLES: u32 = 64;
+
+/// Mean grayscale difference (0-255) under which two frames line up
+const MATCH_THRESHOLD: f32 = 3.0;
+
+/// Captures a region repeatedly while scrolling the content under the cursor,
+/// stitching the frames into one tall image. Stops when the content stops moving.
+pub async fn capture_scrolling(
+    monitor_point: (i32, i32),
+    region: Region,
+    placement: Placement,
+) -> Result<RgbaImage, String> {
+    let mut stitched =
+        screenshot::capture_region_image(monitor_point, region, placement, None).await?;
+    let mut previous = stitched.clone();
+
+    for _ in 1..MAX_FRAMES {
+        scroll(SCROLL_CLICKS).await?;
+        tokio::time::sleep(SETTLE_DELAY).await;
+
+        let frame =
+            screenshot::capture_region_image(monitor_point, region, placement, None).await?;
+        if frame.dimensions() != previous.dimensions() {
+            break;
+        }
+
+        let (frame, offset) = tokio::task::spawn_blocking(move || {
+            let offset = find_scroll_offset(&previous, &frame);
+            (frame, offset)
+        })
+        .await
+        .map_err(|e| format!("Screenshot task failed: {}", e))?;
+
+        match offset {
+            // Nothing moved, the end of the content was reached
+            Some(0) => break,
+            Some(offset) => stitched = append_rows(&stitched, &frame, offset),
+            None => {
+                // Scrolled past the overlap, or fixed bars cover it. Appending the frame
+                // anyway would repeat those bars or leave a gap, so keep what lines up
+                eprintln!("Warning: Scrolled frames don't line up, stopping the capture");
+                break;
+            }
+        }
+
+        if stitched.height() >= MAX_HEIGHT {
+            break;
+        }
+        previous = frame;
+    }
+
+    Ok(stitched)
+}
+
+/// Splits a tall image into tiles of at most `tile_height` rows (0 keeps one image)
+pub fn split_tiles(image: RgbaImage, tile_height: u32) -> Vec<RgbaImage> {
+    if tile_height == 0 || image.height() <= tile_height {
+        return vec![image];
+    }
+
+    (0..image.height())
+        .step_by(tile_height as usize)
+        .map(|top| {
+            let height = tile_height.min(image.height() - top);
+            imageops::crop_imm(&image, 0, top, image.width(), height).to_image()
+        })
+        .collect()
+}
+
+/// How many rows the content moved up between two frames of the same size.
+/// `Some(0)` means the frames are the same, `None` that no offset lines them up.
+pub fn find_scroll_offset(previous: &RgbaImage, next: &RgbaImage) -> Option<u32> {
+    let height = previous.height().min(next.height()) as usize;
+    if height == 0 {
+        return None;
+    }
+
+    let previous_rows = row_signatures(previous);
+    let next_rows = row_signatures(next);
+
+    // Line up the bottom of the previous frame with the top of the next one,
+    // keeping at least an eighth of the frame overlapping
+    let min_overlap = (height / 8).max(8).min(height);
+    let score = |offset: usize| {
+        let overlap = height - offset;
+        let total: f32 = (0..overlap)
+            .map(|row| row_distance(&previous_rows[offset + row], &next_rows[row]))
+            .sum();
+        total / overlap as f32
+    };
+
+    if score(0) <= MATCH_THRESHOLD {
+        return Some(0);
+    }
+
+    let (offset, best) = (1..=height - min_overlap)
+        .map(|offset| (offset, score(offset)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    (best <= MATCH_THRESHOLD).then_some(offset as u32)
+}
+
+/// Appends the bottom `rows` rows of `frame` below `stitched`
+fn append_rows(stitched: &RgbaImage, frame: &RgbaImage, rows: u32) -> RgbaImage {
+    let rows = rows.min(frame.height());
+    let mut combined = RgbaImage::new(stitched.width(), stitched.height() + rows);
+    imageops::replace(&mut combined, stitched, 0, 0);
+
+    let new_rows = imageops::crop_imm(frame, 0, frame.height() - rows, frame.width(), rows);
+    imageops::replace(&mut combined, &*new_rows, 0, stitched.height() as i64);
+
+    combined
+}
+
+/// Grayscale samples across each row, cheap enough to compare every offset
+fn row_signatures(image: &RgbaImage) -> Vec<Vec<f32>> {
+    let samples = ROW_SAMPLES.min(image.width()).max(1);
+    (0..image.height())
+        .map(|y| {
+            (0..samples)
+                .map(|i| {
+                    let x = i * image.width() / samples;
+                    let [r, g, b, _] = image.get_pixel(x, y).0;
+                    0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn row_distance(a: &[f32], b: &[f32]) -> f32 {
+    let total: f32 = a.iter().zip(b).map(|(a, b)| (a - b).abs()).sum();
+    total / a.len().max(1) as f32
+}
+
+/// Scrolls the content under the cursor down by mouse wheel clicks
+async fn scroll(clicks: i32) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        let mut enigo = Enigo::new(&Settings::default())
+            .map_err(|e| format!("Failed to initialize enigo: {:?}", e))?;
+        enigo
+            .scroll(clicks, Axis::Vertical)
+            .map_err(|e| format!("Failed to scroll: {:?}", e))
+    })
+    .await
+    .map_err(|e| format!("Scroll task failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    // A tall page of distinct rows, viewed through a window of `height` rows at `top`
+    fn page_view(top: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(40, height, |x, y| {
+            let row = top + y;
+            let shade = ((row * 37 + x * 3) % 251) as u8;
+            Rgba([shade, shade.wrapping_mul(3), row as u8, 255])
+        })
+    }
+
+    #[test]
+    fn finds_scroll_offset_between_frames() {
+        let previous = page_view(0, 120);
+        let next = page_view(45, 120);
+
+        assert_eq!(find_scroll_offset(&previous, &next), Some(45));
+    }
+
+    #[test]
+    fn identical_frames_have_zero_offset() {
+        let frame = page_view(10, 120);
+
+        assert_eq!(find_scroll_offset(&frame, &frame.clone()), Some(0));
+    }
+
+    #[test]
+    fn frames_without_overlap_have_no_offset() {
+        let previous = page_view(0, 120);
+        let next = page_view(500, 120);
+
+        assert_eq!(find_scroll_offset(&previous, &next), None);
+    }
+
+    #[test]
+    fn stitching_reproduces_the_page() {
+        let first = page_view(0, 120);
+        let second = page_view(45, 120);
+        let offset = find_scroll_offset(&first, &second).unwrap();
+
+        assert_eq!(append_rows(&first, &second, offset), page_view(0, 165));
+    }
+
+    #[test]
+    fn splits_tall_images_into_tiles() {
+        let tiles = split_tiles(page_view(0, 250), 100);
+
+        assert_eq!(
+            tiles.iter().map(|t| t.height()).collect::<Vec<_>>(),
+            vec![100, 100, 50]
+        );
+        assert_eq!(tiles[1], page_view(100, 100));
+        assert_eq!(split_tiles(page_view(0, 250), 0).len(), 1);
+    }
+}
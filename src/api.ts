@@ -1,5 +1,16 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { AppConfig } from "./types";
+import type {
+  AppConfig,
+  Attachment,
+  Conversation,
+  ConversationSummary,
+  HistorySearchQuery,
+  PromptContext,
+  SearchHit,
+  SavedScreenshot,
+  ScreenshotInfo,
+  WindowInfo,
+} from "./types";
 
 export async function loadConfig(): Promise<AppConfig> {
   return await invoke<AppConfig>("load_config");
@@ -21,6 +32,14 @@ export async function getCapturedText(): Promise<string> {
   return await invoke<string>("get_captured_text");
 }
 
+// Fills the {app_name} and {window_title} placeholders for the captured window
+// and builds the window context message when it is enabled
+export async function applyWindowContext(
+  prompt: string,
+): Promise<PromptContext> {
+  return await invoke<PromptContext>("apply_window_context", { prompt });
+}
+
 export async function resizePopupWindow(
   width: number,
   height: number,
@@ -53,6 +72,54 @@ export async function clearScreenshots(): Promise<void> {
   await invoke("clear_screenshots");
 }
 
+export async function getScreenshotInfo(): Promise<ScreenshotInfo[]> {
+  return await invoke<ScreenshotInfo[]>("get_screenshot_info");
+}
+
+export async function listScreenshotHistory(
+  limit?: number,
+): Promise<SavedScreenshot[]> {
+  return await invoke<SavedScreenshot[]>("list_screenshot_history", { limit });
+}
+
+export async function reattachScreenshot(path: string): Promise<string> {
+  return await invoke<string>("reattach_screenshot", { path });
+}
+
+// Conversation history API functions
+export async function listHistory(
+  limit?: number,
+): Promise<ConversationSummary[]> {
+  return await invoke<ConversationSummary[]>("list_history", { limit });
+}
+
+export async function searchHistory(
+  query: HistorySearchQuery,
+  limit?: number,
+): Promise<SearchHit[]> {
+  return await invoke<SearchHit[]>("search_history", { query, limit });
+}
+
+export async function hideHistorySearchWindow(): Promise<void> {
+  await invoke("hide_history_search_window");
+}
+
+export async function getConversation(id: number): Promise<Conversation> {
+  return await invoke<Conversation>("get_conversation", { id });
+}
+
+export async function resumeConversation(id: number): Promise<void> {
+  await invoke("resume_conversation", { id });
+}
+
+export async function deleteHistory(id: number): Promise<void> {
+  await invoke("delete_history", { id });
+}
+
+export async function clearHistory(): Promise<void> {
+  await invoke("clear_history");
+}
+
 export async function removeScreenshot(index: number): Promise<void> {
   await invoke("remove_screenshot", { index });
 }
@@ -71,10 +138,55 @@ export async function captureScreenshotRegion(
   });
 }
 
+// Attachment API functions
+export async function attachFile(path: string): Promise<void> {
+  await invoke("attach_file", { path });
+}
+
+export async function listAttachments(): Promise<Attachment[]> {
+  return await invoke<Attachment[]>("list_attachments");
+}
+
+export async function removeAttachment(index: number): Promise<void> {
+  await invoke("remove_attachment", { index });
+}
+
+export async function clearAttachments(): Promise<void> {
+  await invoke("clear_attachments");
+}
+
+export async function pasteClipboardImage(): Promise<string> {
+  return await invoke<string>("paste_clipboard_image");
+}
+
+export async function listWindows(): Promise<WindowInfo[]> {
+  return await invoke<WindowInfo[]>("list_windows");
+}
+
+export async function captureWindow(windowId: number): Promise<string> {
+  return await invoke<string>("capture_window", { windowId });
+}
+
 export async function showScreenshotSelector(): Promise<void> {
   await invoke("show_screenshot_selector");
 }
 
+export async function isOcrReady(): Promise<boolean> {
+  return await invoke<boolean>("is_ocr_ready");
+}
+
+export async function downloadOcrModels(): Promise<void> {
+  await invoke("download_ocr_models");
+}
+
+export async function setHotkeysPaused(paused: boolean): Promise<void> {
+  await invoke("set_hotkeys_paused", { paused });
+}
+
+export async function isHotkeysPaused(): Promise<boolean> {
+  return await invoke<boolean>("is_hotkeys_paused");
+}
+
 export async function exportConfig(): Promise<string> {
   return await invoke<string>("export_config");
 }
@@ -1,14 +1,54 @@
 import { useState, useEffect } from "react";
-import { loadConfig, saveConfig, exportConfig, importConfig } from "../api";
-import type { AppConfig, ModelConfig, QuestionTemplate } from "../types";
+import {
+  loadConfig,
+  saveConfig,
+  exportConfig,
+  importConfig,
+  isOcrReady,
+  downloadOcrModels,
+  clearHistory,
+} from "../api";
+import type {
+  AppConfig,
+  ModelConfig,
+  OcrConfig,
+  QuestionTemplate,
+  RedactionConfig,
+  ScreenshotConfig,
+} from "../types";
 import { save, open } from "@tauri-apps/plugin-dialog";
 import { writeTextFile, readTextFile } from "@tauri-apps/plugin-fs";
 import "./ConfigPage.css";
 
+const defaultScreenshotConfig: ScreenshotConfig = {
+  max_dimension: 2048,
+  format: "png",
+  quality: 85,
+};
+
+const defaultOcrConfig: OcrConfig = {
+  model_dir: "",
+};
+
+const defaultRedactionConfig: RedactionConfig = {
+  enabled: false,
+  emails: true,
+  api_keys: true,
+  card_numbers: true,
+};
+
+const redactionPatterns: { key: keyof RedactionConfig; label: string }[] = [
+  { key: "emails", label: "Email addresses" },
+  { key: "api_keys", label: "API keys and tokens" },
+  { key: "card_numbers", label: "Credit card numbers" },
+];
+
 export default function ConfigPage() {
   const [config, setConfig] = useState<AppConfig | null>(null);
   const [loading, setLoading] = useState(true);
   const [saving, setSaving] = useState(false);
+  const [ocrReady, setOcrReady] = useState(false);
+  const [downloadingOcr, setDownloadingOcr] = useState(false);
   const [activeTab, setActiveTab] = useState<
     "models" | "templates" | "hotkeys" | "appearance"
   >("models");
@@ -17,6 +57,12 @@ export default function ConfigPage() {
     loadConfiguration();
   }, []);
 
+  useEffect(() => {
+    isOcrReady()
+      .then(setOcrReady)
+      .catch((error) => console.error("Failed to check OCR models:", error));
+  }, [config?.ocr?.model_dir]);
+
   const loadConfiguration = async () => {
     try {
       const loadedConfig = await loadConfig();
@@ -44,6 +90,72 @@ export default function ConfigPage() {
     }
   };
 
+  const handleChooseScreenshotFolder = async () => {
+    if (!config) return;
+
+    try {
+      const folder = await open({ directory: true, multiple: false });
+      if (typeof folder === "string") {
+        setConfig({
+          ...config,
+          screenshot: {
+            ...defaultScreenshotConfig,
+            ...config.screenshot,
+            save_folder: folder,
+          },
+        });
+      }
+    } catch (error) {
+      console.error("Failed to choose folder:", error);
+    }
+  };
+
+  const handleChooseOcrFolder = async () => {
+    if (!config) return;
+
+    try {
+      const folder = await open({ directory: true, multiple: false });
+      if (typeof folder === "string") {
+        setConfig({
+          ...config,
+          ocr: { ...defaultOcrConfig, ...config.ocr, model_dir: folder },
+        });
+      }
+    } catch (error) {
+      console.error("Failed to choose folder:", error);
+    }
+  };
+
+  const handleDownloadOcrModels = async () => {
+    setDownloadingOcr(true);
+    try {
+      // Downloads go to the saved folder, so save any change first
+      if (config) {
+        await saveConfig(config);
+      }
+      await downloadOcrModels();
+      setOcrReady(await isOcrReady());
+    } catch (error) {
+      console.error("Failed to download OCR models:", error);
+      alert(`Failed to download OCR models: ${error}`);
+    } finally {
+      setDownloadingOcr(false);
+    }
+  };
+
+  const handleClearHistory = async () => {
+    if (!confirm("Delete all saved conversations and their screenshots?")) {
+      return;
+    }
+    try {
+      await clearHistory();
+      alert("Conversation history cleared.");
+    } catch (error) {
+      console.error("Failed to clear history:", error);
+      alert(`Failed to clear history: ${error}`);
+    }
+  };
+
   const handleExport = async () => {
     try {
       console.log("Starting export...");
@@ -198,7 +310,7 @@ export default function ConfigPage() {
   const updateTemplate = (
     index: number,
     field: keyof QuestionTemplate,
-    value: string | null | boolean,
+    value: string | null | boolean | string[],
   ) => {
     if (!config) return;
     const newTemplates = [...config.templates];
@@ -206,6 +318,10 @@ export default function ConfigPage() {
     setConfig({ ...config, templates: newTemplates });
   };
 
+  // Blank entries are kept while typing and ignored by the backend
+  const parseAppRules = (value: string): string[] =>
+    value ? value.split(",").map((rule) => rule.trim()) : [];
+
   const removeTemplate = (index: number) => {
     if (!config) return;
     const newTemplates = config.templates.filter((_, i) => i !== index);
@@ -381,6 +497,68 @@ export default function ConfigPage() {
               </div>
             </div>
 
+            <div className="form-group">
+              <label className="checkbox-label">
+                <input
+                  type="checkbox"
+                  checked={config.include_window_context || false}
+                  onChange={(e) =>
+                    setConfig({
+                      ...config,
+                      include_window_context: e.target.checked,
+                    })
+                  }
+                />
+                <span>Include active window as context</span>
+              </label>
+              <p className="help-text">
+                Sends the focused application and window title along with the
+                selected text. Template prompts can also use the{" "}
+                {"{app_name}"} and {"{window_title}"} placeholders.
+              </p>
+            </div>
+            <div className="form-group">
+              <label className="checkbox-label">
+                <input
+                  type="checkbox"
+                  checked={config.save_history ?? false}
+                  onChange={(e) =>
+                    setConfig({
+                      ...config,
+                      save_history: e.target.checked,
+                    })
+                  }
+                />
+                <span>Save conversation history</span>
+              </label>
+              <p className="help-text">
+                Keeps prompts and responses from the popup and background
+                templates in a local database. Screenshots are only kept when
+                saving screenshots to disk is enabled.
+              </p>
+              <button onClick={handleClearHistory}>Clear History</button>
+            </div>
+            {config.save_history && (
+              <div className="form-group">
+                <label>Conversations to Keep:</label>
+                <input
+                  type="number"
+                  min="0"
+                  value={config.history_retention_count ?? 500}
+                  onChange={(e) =>
+                    setConfig({
+                      ...config,
+                      history_retention_count: Number(e.target.value),
+                    })
+                  }
+                />
+                <p className="help-text">
+                  The least recently used conversations are deleted beyond this
+                  count. Use 0 to keep everything. Default: 500
+                </p>
+              </div>
+            )}
+
             {config.templates.map((template, index) => (
               <div key={template.id} className="template-card">
                 <div className="card-header">
@@ -455,6 +633,81 @@ export default function ConfigPage() {
                     configuration.
                   </p>
                 </div>
+                <div className="form-group">
+                  <label>Input:</label>
+                  <select
+                    value={template.input_source || "selection"}
+                    onChange={(e) =>
+                      updateTemplate(index, "input_source", e.target.value)
+                    }
+                  >
+                    <option value="selection">Selected text only</option>
+                    <option value="selection_or_clipboard">
+                      Selected text, or clipboard if nothing is selected
+                    </option>
+                    <option value="clipboard">Clipboard only</option>
+                    <option value="ask">
+                      Selected text, or ask in the popup
+                    </option>
+                  </select>
+                  <p className="help-text">
+                    Background templates without any input are skipped with a
+                    notification instead of sending an empty request.
+                  </p>
+                </div>
+                <div className="form-group">
+                  <label>Input Format:</label>
+                  <select
+                    value={template.input_format || "plain"}
+                    onChange={(e) =>
+                      updateTemplate(index, "input_format", e.target.value)
+                    }
+                  >
+                    <option value="plain">Plain text</option>
+                    <option value="rich">
+                      Rich text (tables, links and code as Markdown)
+                    </option>
+                  </select>
+                  <p className="help-text">
+                    Rich text copies the selection and converts its formatting
+                    to Markdown when the application provides HTML.
+                  </p>
+                </div>
+                <div className="form-group">
+                  <label>Only in apps (optional):</label>
+                  <input
+                    type="text"
+                    value={(template.include_apps || []).join(", ")}
+                    onChange={(e) =>
+                      updateTemplate(
+                        index,
+                        "include_apps",
+                        parseAppRules(e.target.value),
+                      )
+                    }
+                    placeholder="e.g., code, outlook, title:Gmail"
+                  />
+                  <label>Not in apps (optional):</label>
+                  <input
+                    type="text"
+                    value={(template.exclude_apps || []).join(", ")}
+                    onChange={(e) =>
+                      updateTemplate(
+                        index,
+                        "exclude_apps",
+                        parseAppRules(e.target.value),
+                      )
+                    }
+                    placeholder="e.g., chrome, firefox"
+                  />
+                  <p className="help-text">
+                    Comma-separated app or executable names, * matching any
+                    characters (e.g., visual studio*), or title:text to match
+                    part of the window title. Templates sharing a hotkey run the
+                    one whose rules match the focused app, falling back to a
+                    template without "Only in apps" rules.
+                  </p>
+                </div>
                 <div className="form-group">
                   <label className="checkbox-label">
                     <input
@@ -477,6 +730,23 @@ export default function ConfigPage() {
                     (copy/replace).
                   </p>
                 </div>
+                <div className="form-group">
+                  <label className="checkbox-label">
+                    <input
+                      type="checkbox"
+                      checked={template.hold_mode || false}
+                      onChange={(e) =>
+                        updateTemplate(index, "hold_mode", e.target.checked)
+                      }
+                    />
+                    <span>Press and hold hotkey</span>
+                  </label>
+                  <p className="help-text">
+                    While the hotkey is held, the answer streams into the popup
+                    as a preview. Releasing it confirms the configured action;
+                    releasing before the answer completes cancels the request.
+                  </p>
+                </div>
               </div>
             ))}
           </div>
@@ -507,6 +777,29 @@ export default function ConfigPage() {
                 Examples: Alt+S, Ctrl+Shift+A, CommandOrControl+Q
               </p>
             </div>
+            <div className="form-group">
+              <label className="checkbox-label">
+                <input
+                  type="checkbox"
+                  checked={config.hotkeys.popup_hold_mode || false}
+                  onChange={(e) =>
+                    setConfig({
+                      ...config,
+                      hotkeys: {
+                        ...config.hotkeys,
+                        popup_hold_mode: e.target.checked,
+                      },
+                    })
+                  }
+                />
+                <span>Replace on popup hotkey release</span>
+              </label>
+              <p className="help-text">
+                When the popup is open, hold the popup hotkey and release it to
+                replace the selection with the latest answer. Releasing while
+                an answer is still streaming cancels it.
+              </p>
+            </div>
             <div className="form-group">
               <label>Screenshot Hotkey:</label>
               <input
@@ -532,6 +825,392 @@ export default function ConfigPage() {
                 configuration.
               </p>
             </div>
+            <div className="form-group">
+              <label>Screenshot Mode:</label>
+              <select
+                value={config.hotkeys.screenshot_mode || "region"}
+                onChange={(e) =>
+                  setConfig({
+                    ...config,
+                    hotkeys: {
+                      ...config.hotkeys,
+                      screenshot_mode: e.target.value as "region" | "window",
+                    },
+                  })
+                }
+              >
+                <option value="region">Select a region</option>
+                <option value="window">
+                  Capture the window under the cursor
+                </option>
+              </select>
+            </div>
+            <div className="form-group">
+              <label>Clipboard Image Hotkey:</label>
+              <input
+                type="text"
+                value={config.hotkeys.clipboard_image_hotkey || ""}
+                onChange={(e) =>
+                  setConfig({
+                    ...config,
+                    hotkeys: {
+                      ...config.hotkeys,
+                      clipboard_image_hotkey: e.target.value || null,
+                    },
+                  })
+                }
+                placeholder="Optional, e.g. Alt+Shift+V"
+              />
+              <p className="help-text">
+                Attaches the image on the clipboard and opens the popup window.
+              </p>
+            </div>
+            <div className="form-group">
+              <label>Scrolling Capture Hotkey:</label>
+              <input
+                type="text"
+                value={config.hotkeys.scroll_capture_hotkey || ""}
+                onChange={(e) =>
+                  setConfig({
+                    ...config,
+                    hotkeys: {
+                      ...config.hotkeys,
+                      scroll_capture_hotkey: e.target.value || null,
+                    },
+                  })
+                }
+                placeholder="Optional, e.g. Alt+Shift+L"
+              />
+              <p className="help-text">
+                Select a region over a long page or chat; it is captured while
+                scrolling down and stitched into one image.
+              </p>
+            </div>
+            <div className="form-group">
+              <label>History Search Hotkey:</label>
+              <input
+                type="text"
+                value={config.hotkeys.history_search_hotkey || ""}
+                onChange={(e) =>
+                  setConfig({
+                    ...config,
+                    hotkeys: {
+                      ...config.hotkeys,
+                      history_search_hotkey: e.target.value || null,
+                    },
+                  })
+                }
+                placeholder="Optional, e.g. Alt+Shift+H"
+              />
+              <p className="help-text">
+                Opens a window to search past prompts and responses.
+              </p>
+            </div>
+            <div className="form-group">
+              <label>Scrolling Capture Tile Height (px):</label>
+              <input
+                type="number"
+                min="0"
+                value={config.screenshot?.scroll_tile_height ?? 1600}
+                onChange={(e) =>
+                  setConfig({
+                    ...config,
+                    screenshot: {
+                      ...defaultScreenshotConfig,
+                      ...config.screenshot,
+                      scroll_tile_height: Number(e.target.value),
+                    },
+                  })
+                }
+              />
+              <p className="help-text">
+                Long captures are split into screenshots of this height so they
+                stay readable for vision models. Use 0 to send one tall image.
+                Default: 1600
+              </p>
+            </div>
+            <div className="form-group">
+              <label>Screenshot Max Size (px):</label>
+              <input
+                type="number"
+                min="0"
+                max="8192"
+                value={config.screenshot?.max_dimension ?? 2048}
+                onChange={(e) =>
+                  setConfig({
+                    ...config,
+                    screenshot: {
+                      ...defaultScreenshotConfig,
+                      ...config.screenshot,
+                      max_dimension: Number(e.target.value),
+                    },
+                  })
+                }
+              />
+              <p className="help-text">
+                Screenshots are downscaled so their longest edge fits this size
+                before being sent. Use 0 to keep full resolution. Default: 2048
+              </p>
+            </div>
+            <div className="form-group">
+              <label>Screenshot Format:</label>
+              <select
+                value={config.screenshot?.format || "png"}
+                onChange={(e) =>
+                  setConfig({
+                    ...config,
+                    screenshot: {
+                      ...defaultScreenshotConfig,
+                      ...config.screenshot,
+                      format: e.target.value as ScreenshotConfig["format"],
+                    },
+                  })
+                }
+              >
+                <option value="png">PNG (lossless)</option>
+                <option value="jpeg">JPEG (smallest)</option>
+                <option value="webp">WebP (lossless)</option>
+              </select>
+            </div>
+            {config.screenshot?.format === "jpeg" && (
+              <div className="form-group">
+                <label>JPEG Quality:</label>
+                <input
+                  type="number"
+                  min="1"
+                  max="100"
+                  value={config.screenshot.quality}
+                  onChange={(e) =>
+                    setConfig({
+                      ...config,
+                      screenshot: {
+                        ...defaultScreenshotConfig,
+                        ...config.screenshot,
+                        quality: Number(e.target.value),
+                      },
+                    })
+                  }
+                />
+                <p className="help-text">1-100. Default: 85</p>
+              </div>
+            )}
+            <div className="form-group">
+              <label className="checkbox-label">
+                <input
+                  type="checkbox"
+                  checked={config.screenshot?.save_to_disk || false}
+                  onChange={(e) =>
+                    setConfig({
+                      ...config,
+                      screenshot: {
+                        ...defaultScreenshotConfig,
+                        ...config.screenshot,
+                        save_to_disk: e.target.checked,
+                      },
+                    })
+                  }
+                />
+                <span>Save screenshots to disk</span>
+              </label>
+              <p className="help-text">
+                Keeps a timestamped copy of every capture so it can be attached
+                again later from the popup.
+              </p>
+            </div>
+            {config.screenshot?.save_to_disk && (
+              <>
+                <div className="form-group">
+                  <label>Screenshot Folder:</label>
+                  <input
+                    type="text"
+                    value={config.screenshot.save_folder || ""}
+                    onChange={(e) =>
+                      setConfig({
+                        ...config,
+                        screenshot: {
+                          ...defaultScreenshotConfig,
+                          ...config.screenshot,
+                          save_folder: e.target.value,
+                        },
+                      })
+                    }
+                    placeholder="Default: app data folder"
+                  />
+                  <button onClick={handleChooseScreenshotFolder} type="button">
+                    Browse...
+                  </button>
+                </div>
+                <div className="form-group">
+                  <label>Screenshots to Keep:</label>
+                  <input
+                    type="number"
+                    min="0"
+                    value={config.screenshot.retention_count ?? 200}
+                    onChange={(e) =>
+                      setConfig({
+                        ...config,
+                        screenshot: {
+                          ...defaultScreenshotConfig,
+                          ...config.screenshot,
+                          retention_count: Number(e.target.value),
+                        },
+                      })
+                    }
+                  />
+                  <p className="help-text">
+                    The oldest screenshots are deleted beyond this count. Use 0
+                    to keep everything. Default: 200
+                  </p>
+                </div>
+              </>
+            )}
+            <div className="form-group">
+              <label>Models Without Vision:</label>
+              <select
+                value={config.vision_fallback || "ocr"}
+                onChange={(e) =>
+                  setConfig({
+                    ...config,
+                    vision_fallback: e.target
+                      .value as AppConfig["vision_fallback"],
+                  })
+                }
+              >
+                <option value="ocr">Send the text recognized by OCR</option>
+                <option value="route">Switch to a vision-capable model</option>
+                <option value="block">Refuse to send screenshots</option>
+              </select>
+              <p className="help-text">
+                What happens when screenshots are sent to a model without image
+                support. OCR refuses to send them until its models are
+                downloaded. Default: OCR
+              </p>
+            </div>
+            {config.vision_fallback === "route" && (
+              <div className="form-group">
+                <label>Vision Model:</label>
+                <select
+                  value={config.vision_model || ""}
+                  onChange={(e) =>
+                    setConfig({ ...config, vision_model: e.target.value })
+                  }
+                >
+                  <option value="">First vision-capable model</option>
+                  {config.models
+                    .filter((model) => model.supports_vision)
+                    .map((model) => (
+                      <option key={model.name} value={model.name}>
+                        {model.name}
+                      </option>
+                    ))}
+                </select>
+                <p className="help-text">
+                  The model that screenshots are sent to instead
+                </p>
+              </div>
+            )}
+            <div className="form-group">
+              <label>OCR Model Folder:</label>
+              <input
+                type="text"
+                value={config.ocr?.model_dir || ""}
+                onChange={(e) =>
+                  setConfig({
+                    ...config,
+                    ocr: {
+                      ...defaultOcrConfig,
+                      ...config.ocr,
+                      model_dir: e.target.value,
+                    },
+                  })
+                }
+                placeholder="Default: app data folder"
+              />
+              <button onClick={handleChooseOcrFolder} type="button">
+                Browse...
+              </button>
+              <button
+                onClick={handleDownloadOcrModels}
+                type="button"
+                disabled={downloadingOcr}
+              >
+                {downloadingOcr ? "Downloading..." : "Download Models"}
+              </button>
+              <p className="help-text">
+                {ocrReady
+                  ? "OCR models are installed."
+                  : "OCR models are not installed yet (about 12 MB)."}
+              </p>
+            </div>
+            <div className="form-group">
+              <label className="checkbox-label">
+                <input
+                  type="checkbox"
+                  checked={config.redaction?.enabled || false}
+                  onChange={(e) =>
+                    setConfig({
+                      ...config,
+                      redaction: {
+                        ...defaultRedactionConfig,
+                        ...config.redaction,
+                        enabled: e.target.checked,
+                      },
+                    })
+                  }
+                />
+                <span>Redact sensitive text in screenshots</span>
+              </label>
+              <p className="help-text">
+                Screenshots are scanned with OCR and matching text is pixelated
+                before it leaves the app. Needs the OCR models. Use the blur
+                tool in the region selector (Shift + release) to hide anything
+                else by hand.
+              </p>
+            </div>
+            {config.redaction?.enabled &&
+              redactionPatterns.map(({ key, label }) => (
+                <div className="form-group" key={key}>
+                  <label className="checkbox-label">
+                    <input
+                      type="checkbox"
+                      checked={config.redaction?.[key] ?? true}
+                      onChange={(e) =>
+                        setConfig({
+                          ...config,
+                          redaction: {
+                            ...defaultRedactionConfig,
+                            ...config.redaction,
+                            [key]: e.target.checked,
+                          },
+                        })
+                      }
+                    />
+                    <span>{label}</span>
+                  </label>
+                </div>
+              ))}
+            <div className="form-group">
+              <label>Screenshot to Text Hotkey:</label>
+              <input
+                type="text"
+                value={config.ocr?.hotkey || ""}
+                onChange={(e) =>
+                  setConfig({
+                    ...config,
+                    ocr: {
+                      ...defaultOcrConfig,
+                      ...config.ocr,
+                      hotkey: e.target.value || null,
+                    },
+                  })
+                }
+                placeholder="Optional, e.g. Alt+Shift+T"
+              />
+              <p className="help-text">
+                Select a screen region and copy the text in it to the
+                clipboard.
+              </p>
+            </div>
           </div>
         )}
 
@@ -0,0 +1,235 @@
+import { useState, useEffect, useRef } from "react";
+import { listen } from "@tauri-apps/api/event";
+import {
+  loadConfig,
+  searchHistory,
+  getConversation,
+  hideHistorySearchWindow,
+  resumeConversation,
+} from "../api";
+import type { AppConfig, SearchHit } from "../types";
+import "./HistorySearch.css";
+
+const MATCH_START = "\u0002";
+const MATCH_END = "\u0003";
+
+// Delay before searching while typing
+const SEARCH_DELAY_MS = 150;
+
+// Renders a search snippet with the matched terms highlighted
+function Snippet({ text }: { text: string }) {
+  const parts = text.split(MATCH_START);
+  return (
+    <>
+      {parts.map((part, index) => {
+        if (index === 0) return part;
+        const [match, rest] = part.split(MATCH_END);
+        return (
+          <span key={index}>
+            <mark>{match}</mark>
+            {rest}
+          </span>
+        );
+      })}
+    </>
+  );
+}
+
+// Midnight of a yyyy-mm-dd date input, in milliseconds
+function dateValue(value: string, endOfDay = false): number | null {
+  if (!value) return null;
+  const date = new Date(`${value}T00:00:00`);
+  if (endOfDay) date.setDate(date.getDate() + 1);
+  return date.getTime();
+}
+
+function HistorySearch() {
+  const [config, setConfig] = useState<AppConfig | null>(null);
+  const [query, setQuery] = useState("");
+  const [template, setTemplate] = useState("");
+  const [model, setModel] = useState("");
+  const [fromDate, setFromDate] = useState("");
+  const [toDate, setToDate] = useState("");
+  const [hits, setHits] = useState<SearchHit[]>([]);
+  const [selectedIndex, setSelectedIndex] = useState(0);
+  const [error, setError] = useState<string | null>(null);
+  const inputRef = useRef<HTMLInputElement>(null);
+
+  useEffect(() => {
+    loadConfig()
+      .then(setConfig)
+      .catch((err) => console.error("Failed to load config:", err));
+    inputRef.current?.focus();
+
+    // The window is reused, so start over each time it opens
+    const unlistenOpened = listen("history-search-opened", () => {
+      setQuery("");
+      setHits([]);
+      setError(null);
+      loadConfig()
+        .then(setConfig)
+        .catch((err) => console.error("Failed to load config:", err));
+      setTimeout(() => inputRef.current?.focus(), 50);
+    });
+
+    return () => {
+      unlistenOpened.then((fn) => fn());
+    };
+  }, []);
+
+  useEffect(() => {
+    const timer = setTimeout(async () => {
+      try {
+        const results = await searchHistory({
+          query,
+          template: template || null,
+          model: model || null,
+          from: dateValue(fromDate),
+          to: dateValue(toDate, true),
+        });
+        setHits(results);
+        setSelectedIndex(0);
+        setError(null);
+      } catch (err) {
+        console.error("Failed to search history:", err);
+        setError(String(err));
+      }
+    }, SEARCH_DELAY_MS);
+
+    return () => clearTimeout(timer);
+  }, [query, template, model, fromDate, toDate]);
+
+  const copyResponse = async (hit: SearchHit) => {
+    try {
+      const conversation = await getConversation(hit.conversation_id);
+      const exchange = conversation.exchanges.find(
+        (e) => e.id === hit.exchange_id,
+      );
+      if (exchange) {
+        await navigator.clipboard.writeText(exchange.output);
+      }
+      await hideHistorySearchWindow();
+    } catch (err) {
+      console.error("Failed to copy response:", err);
+      setError(String(err));
+    }
+  };
+
+  const continueConversation = async (hit: SearchHit) => {
+    try {
+      await hideHistorySearchWindow();
+      await resumeConversation(hit.conversation_id);
+    } catch (err) {
+      console.error("Failed to resume conversation:", err);
+      setError(String(err));
+    }
+  };
+
+  const handleKeyDown = (e: React.KeyboardEvent) => {
+    if (e.key === "Escape") {
+      hideHistorySearchWindow();
+    } else if (e.key === "ArrowDown") {
+      e.preventDefault();
+      setSelectedIndex((index) => Math.min(index + 1, hits.length - 1));
+    } else if (e.key === "ArrowUp") {
+      e.preventDefault();
+      setSelectedIndex((index) => Math.max(index - 1, 0));
+    } else if (e.key === "Enter" && hits[selectedIndex]) {
+      e.preventDefault();
+      if (e.shiftKey) {
+        copyResponse(hits[selectedIndex]);
+      } else {
+        continueConversation(hits[selectedIndex]);
+      }
+    }
+  };
+
+  const templateNames = [
+    ...new Set(config?.templates.map((t) => t.name) ?? []),
+  ];
+  const modelNames = [
+    ...new Set(config?.models.map((m) => m.model_name) ?? []),
+  ];
+
+  return (
+    <div className="history-search" onKeyDown={handleKeyDown}>
+      <div className="history-search-header" data-tauri-drag-region>
+        <input
+          ref={inputRef}
+          type="text"
+          className="history-search-input"
+          value={query}
+          onChange={(e) => setQuery(e.target.value)}
+          placeholder="Search prompts and responses..."
+        />
+      </div>
+      <div className="history-search-filters">
+        <select value={template} onChange={(e) => setTemplate(e.target.value)}>
+          <option value="">Any template</option>
+          {templateNames.map((name) => (
+            <option key={name} value={name}>
+              {name}
+            </option>
+          ))}
+        </select>
+        <select value={model} onChange={(e) => setModel(e.target.value)}>
+          <option value="">Any model</option>
+          {modelNames.map((name) => (
+            <option key={name} value={name}>
+              {name}
+            </option>
+          ))}
+        </select>
+        <input
+          type="date"
+          value={fromDate}
+          onChange={(e) => setFromDate(e.target.value)}
+          title="From"
+        />
+        <input
+          type="date"
+          value={toDate}
+          onChange={(e) => setToDate(e.target.value)}
+          title="To"
+        />
+      </div>
+      {error && <div className="history-search-error">{error}</div>}
+      <div className="history-search-results">
+        {hits.map((hit, index) => (
+          <div
+            key={hit.exchange_id}
+            className={`history-search-hit ${index === selectedIndex ? "selected" : ""}`}
+            onMouseEnter={() => setSelectedIndex(index)}
+            onClick={() => continueConversation(hit)}
+          >
+            <div className="hit-title">{hit.title}</div>
+            <div className="hit-meta">
+              {[
+                hit.template,
+                hit.model,
+                new Date(hit.started_at).toLocaleString(),
+              ]
+                .filter(Boolean)
+                .join(" · ")}
+            </div>
+            <div className="hit-snippet">
+              <Snippet text={hit.input_snippet} />
+            </div>
+            <div className="hit-snippet response">
+              <Snippet text={hit.output_snippet} />
+            </div>
+          </div>
+        ))}
+        {query.trim() && hits.length === 0 && !error && (
+          <div className="history-search-empty">No matches</div>
+        )}
+      </div>
+      <div className="history-search-footer">
+        ↑↓ to select · Enter to continue in the popup · Shift+Enter to copy
+        the response · Esc to close
+      </div>
+    </div>
+  );
+}
+
+export default HistorySearch;
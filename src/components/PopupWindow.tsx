@@ -6,23 +6,42 @@ import rehypeKatex from "rehype-katex";
 import "katex/dist/katex.min.css";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import { listen } from "@tauri-apps/api/event";
+import { open } from "@tauri-apps/plugin-dialog";
 import {
   loadConfig,
   getCapturedText,
+  applyWindowContext,
   resizePopupWindow,
   hidePopupWindow,
   setPopupPinned,
   isPopupPinned,
   replaceTextInSource,
   getScreenshots,
+  getScreenshotInfo,
   clearScreenshots,
   removeScreenshot,
+  attachFile,
+  listAttachments,
+  removeAttachment,
+  clearAttachments,
+  listWindows,
+  captureWindow,
+  pasteClipboardImage,
+  listScreenshotHistory,
+  reattachScreenshot,
 } from "../api";
 import {
   streamAiResponse,
+  cancelAiResponse,
   type Message as AIMessage,
 } from "../services/aiClient";
-import type { AppConfig } from "../types";
+import type {
+  AppConfig,
+  Attachment,
+  SavedScreenshot,
+  ScreenshotInfo,
+  WindowInfo,
+} from "../types";
 import "./PopupWindow.css";
 
 // Preprocess LaTeX delimiters from LLM output
@@ -43,6 +62,13 @@ function preprocessLatex(content: string): string {
   return inlineProcessed;
 }
 
+// Format a byte count for display, e.g. "1.2 MB"
+function formatBytes(bytes: number): string {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(0)} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
+
 // CodeBlock component with copy button
 function CodeBlock({ children }: { children: React.ReactNode }) {
   const [copied, setCopied] = useState(false);
@@ -102,11 +128,29 @@ export default function PopupWindow() {
   const [suggestionIndex, setSuggestionIndex] = useState(0);
   const [isPinned, setIsPinned] = useState(false);
   const [screenshots, setScreenshots] = useState<string[]>([]);
+  const [screenshotInfo, setScreenshotInfo] = useState<ScreenshotInfo[]>([]);
+  const [attachments, setAttachments] = useState<Attachment[]>([]);
+  const [windowPicker, setWindowPicker] = useState<WindowInfo[] | null>(null);
+  const [historyPicker, setHistoryPicker] = useState<
+    SavedScreenshot[] | null
+  >(null);
   const dropdownRef = useRef<HTMLDivElement>(null);
   const inputRef = useRef<HTMLInputElement>(null);
   const suggestionsRef = useRef<HTMLDivElement>(null);
   const messagesContainerRef = useRef<HTMLDivElement>(null);
   const capturedTextRef = useRef<string>("");
+  // History conversation the current messages are recorded under
+  const conversationIdRef = useRef<number | null>(null);
+  // Pending hold-mode request, confirmed or cancelled on hotkey release
+  const holdRef = useRef<{
+    hotkey: string;
+    action: "none" | "copy" | "replace";
+    response: string | null;
+    cancelled: boolean;
+  } | null>(null);
+  // Latest conversation state for the hotkey-released listener, registered once
+  const messagesRef = useRef<Message[]>([]);
+  const isStreamingRef = useRef(false);
 
   // Window size - use configured values or defaults
   const POPUP_WIDTH = config?.popup_width || 500;
@@ -137,6 +181,104 @@ export default function PopupWindow() {
     };
   }, [messages]);
 
+  // Template with "ask" input source fired without input: prefill its command
+  useEffect(() => {
+    const unlistenPrefill = listen<{ id: string; name: string }>(
+      "prefill-template",
+      (event) => {
+        setCustomPrompt(`/${event.payload.name} `);
+        setShowSuggestions(false);
+        setTimeout(() => {
+          inputRef.current?.focus();
+        }, 100);
+      },
+    );
+
+    return () => {
+      unlistenPrefill.then((fn) => fn());
+    };
+  }, []);
+
+  // Stored conversation reopened from history: show it and continue it
+  useEffect(() => {
+    const unlistenResume = listen<{ id: number; messages: Message[] }>(
+      "resume-conversation",
+      (event) => {
+        // Messages arrive oldest first, the UI shows newest first
+        setMessages(event.payload.messages.slice().reverse());
+        conversationIdRef.current = event.payload.id;
+        capturedTextRef.current = "";
+        setCurrentResponse("");
+        setCustomPrompt("");
+        setError(null);
+        // The backend attached the conversation's screenshots for the next message
+        getScreenshots()
+          .then(setScreenshots)
+          .catch((err) => console.error("Failed to load screenshots:", err));
+        setTimeout(() => {
+          inputRef.current?.focus();
+        }, 100);
+      },
+    );
+
+    return () => {
+      unlistenResume.then((fn) => fn());
+    };
+  }, []);
+
+  useEffect(() => {
+    messagesRef.current = messages;
+    isStreamingRef.current = isStreaming;
+  }, [messages, isStreaming]);
+
+  // Hold-mode hotkey released: confirm a finished answer or cancel the request
+  useEffect(() => {
+    const unlistenReleased = listen<{ id: string }>(
+      "hotkey-released",
+      async (event) => {
+        const hold = holdRef.current;
+
+        // Popup hotkey hold: replace with the latest assistant response
+        if (event.payload.id === "popup") {
+          if (isStreamingRef.current) {
+            await cancelAiResponse();
+            return;
+          }
+          const latestAssistantMessage = messagesRef.current.find(
+            (m) => m.role === "assistant",
+          );
+          if (latestAssistantMessage) {
+            handleReplaceResponseInternal(latestAssistantMessage.content);
+          }
+          return;
+        }
+
+        // Only the template hotkey that started this request settles it
+        if (!hold || hold.hotkey !== event.payload.id) {
+          return;
+        }
+
+        holdRef.current = null;
+        if (hold.response !== null) {
+          await runTemplateAction(hold.action, hold.response);
+        } else {
+          // Released before the answer completed
+          hold.cancelled = true;
+          try {
+            await cancelAiResponse();
+          } catch (err) {
+            console.error("Failed to cancel response:", err);
+          }
+          hidePopupWindow();
+        }
+      },
+    );
+
+    return () => {
+      unlistenReleased.then((fn) => fn());
+    };
+  }, []);
+
   // Separate effect for execute-template event - only set up when config is loaded
   useEffect(() => {
     if (!config) {
@@ -153,15 +295,32 @@ export default function PopupWindow() {
       id: string;
       prompt: string;
       action: string;
+      hold?: boolean;
+      hotkey?: string;
     }>("execute-template", async (event) => {
       console.log("=== Received execute-template event ===");
       console.log("Event payload:", event.payload);
-      const { prompt, action } = event.payload;
+      const { id, prompt, action, hold, hotkey } = event.payload;
+
+      // Hold mode defers the action until the hotkey is released
+      const holdState = hold
+        ? {
+            hotkey: hotkey ?? "",
+            action: action as "none" | "copy" | "replace",
+            response: null,
+            cancelled: false,
+          }
+        : null;
+      holdRef.current = holdState;
 
       // Wait to ensure popup is fully initialized and text is captured
       console.log("Waiting for popup to initialize and text to be captured...");
       await new Promise((resolve) => setTimeout(resolve, 500));
 
+      if (holdState?.cancelled) {
+        return;
+      }
+
       // Get the captured text and update both state and ref
       let capturedText = "";
       try {
@@ -195,8 +354,14 @@ export default function PopupWindow() {
       try {
         await handleSend(
           prompt,
-          action as "none" | "copy" | "replace",
+          holdState ? "none" : (action as "none" | "copy" | "replace"),
           capturedText,
+          (response) => {
+            if (holdState && !holdState.cancelled) {
+              holdState.response = response;
+            }
+          },
+          config.templates.find((t) => t.id === id)?.name,
         );
         console.log("handleSend completed successfully");
       } catch (err) {
@@ -236,12 +401,28 @@ export default function PopupWindow() {
     }
   }, [messages.length]);
 
+  // Refresh the payload size and token estimate of the attached screenshots
+  useEffect(() => {
+    if (screenshots.length === 0) {
+      setScreenshotInfo([]);
+      return;
+    }
+
+    getScreenshotInfo()
+      .then(setScreenshotInfo)
+      .catch((err) => console.error("Failed to get screenshot info:", err));
+  }, [screenshots]);
+
   // Auto-switch to vision-capable model when screenshots are added
   useEffect(() => {
     if (!config) return;
 
-    // If screenshots exist and current model doesn't support vision
-    if (screenshots.length > 0) {
+    // If screenshots exist and current model doesn't support vision.
+    // Only when routing to a vision model; OCR keeps the model as is.
+    if (
+      screenshots.length > 0 &&
+      (config.vision_fallback ?? "ocr") === "route"
+    ) {
       const currentModel = config.models[config.selected_model_index];
 
       if (!currentModel?.supports_vision) {
@@ -326,6 +507,10 @@ export default function PopupWindow() {
       const loadedScreenshots = await getScreenshots();
       setScreenshots(loadedScreenshots);
 
+      // Load attached files
+      const loadedAttachments = await listAttachments();
+      setAttachments(loadedAttachments);
+
       // Load pinned state
       const pinned = await isPopupPinned();
       setIsPinned(pinned);
@@ -344,6 +529,8 @@ export default function PopupWindow() {
     promptOverride?: string,
     templateAction?: "none" | "copy" | "replace",
     capturedTextOverride?: string,
+    onResponse?: (response: string) => void,
+    templateName?: string,
   ) => {
     if (!config) return;
 
@@ -354,7 +541,12 @@ export default function PopupWindow() {
     }
 
     // Check if model supports vision when screenshots are present
-    if (screenshots.length > 0 && !selectedModel.supports_vision) {
+    // (the backend enforces this too and applies the fallback)
+    if (
+      screenshots.length > 0 &&
+      !selectedModel.supports_vision &&
+      (config.vision_fallback ?? "ocr") === "block"
+    ) {
       setError(
         `Model "${selectedModel.name}" does not support images. Please select a vision-capable model or remove images.`,
       );
@@ -364,6 +556,7 @@ export default function PopupWindow() {
     // Determine the prompt to use and track template action
     let finalPrompt = "";
     let actionToExecute: "none" | "copy" | "replace" = "none";
+    let usedTemplate = templateName ?? null;
 
     if (promptOverride !== undefined) {
       // Use the provided prompt override (from suggestion click or template hotkey)
@@ -386,6 +579,7 @@ export default function PopupWindow() {
             ? `${template.prompt}\n\n${additionalText}`
             : template.prompt;
           actionToExecute = template.action;
+          usedTemplate = template.name;
         } else {
           setError(`Template "${commandName}" not found.`);
           return;
@@ -407,15 +601,40 @@ export default function PopupWindow() {
     // Reverse messages array since UI displays newest first, but API needs oldest first
     const conversationMessages: AIMessage[] = [];
 
+    // Describe the window the text was captured from
+    const windowContext = await applyWindowContext(finalPrompt).catch(
+      (err) => {
+        console.error("Failed to apply window context:", err);
+        return null;
+      },
+    );
+    if (windowContext) {
+      finalPrompt = windowContext.prompt;
+      if (windowContext.system_message) {
+        conversationMessages.push({
+          role: "system",
+          content: windowContext.system_message,
+        });
+      }
+    }
+
     // If capturedTextOverride is provided, add it as the first message
     if (capturedTextOverride) {
       conversationMessages.push({
         role: "user",
         content: capturedTextOverride,
       });
+      // Fresh captured text starts a new conversation in history
+      conversationIdRef.current = null;
     } else {
       // Otherwise include all previous messages in correct order (oldest first)
-      conversationMessages.push(...messages.slice().reverse());
+      // Images are sent as screenshots, the API only takes role and content
+      conversationMessages.push(
+        ...messages
+          .slice()
+          .reverse()
+          .map(({ role, content }) => ({ role, content })),
+      );
     }
 
     // Add current user message
@@ -448,14 +667,26 @@ export default function PopupWindow() {
       console.error("Failed to clear screenshots:", err);
     }
 
+    // Attachments are only sent with the message they were added to
+    const attachmentsForApi = [...attachments];
+    try {
+      await clearAttachments();
+      setAttachments([]);
+    } catch (err) {
+      console.error("Failed to clear attachments:", err);
+    }
+
     try {
       let accumulatedResponse = "";
       await streamAiResponse(
         selectedModel.base_url,
         selectedModel.api_key,
         selectedModel.model_name,
+        usedTemplate,
+        conversationIdRef.current,
         conversationMessages,
         screenshotsForApi,
+        attachmentsForApi,
         {
           onChunk: (chunk) => {
             accumulatedResponse += chunk;
@@ -466,7 +697,11 @@ export default function PopupWindow() {
             setIsStreaming(false);
             setCurrentResponse("");
           },
-          onDone: async () => {
+          onDone: async (conversationId) => {
+            if (conversationId !== null) {
+              conversationIdRef.current = conversationId;
+            }
+
             // Add assistant response to messages
             setMessages((prev) => [
               { role: "assistant", content: accumulatedResponse },
@@ -475,17 +710,10 @@ export default function PopupWindow() {
             setCurrentResponse("");
             setCustomPrompt("");
             setIsStreaming(false);
+            onResponse?.(accumulatedResponse);
 
             // Execute the template action if specified
-            if (actionToExecute === "copy") {
-              try {
-                await navigator.clipboard.writeText(accumulatedResponse);
-              } catch (err) {
-                console.error("Failed to copy:", err);
-              }
-            } else if (actionToExecute === "replace") {
-              handleReplaceResponseInternal(accumulatedResponse);
-            }
+            await runTemplateAction(actionToExecute, accumulatedResponse);
           },
         },
       );
@@ -497,6 +725,21 @@ export default function PopupWindow() {
     }
   };
 
+  const runTemplateAction = async (
+    action: "none" | "copy" | "replace",
+    response: string,
+  ) => {
+    if (action === "copy") {
+      try {
+        await navigator.clipboard.writeText(response);
+      } catch (err) {
+        console.error("Failed to copy:", err);
+      }
+    } else if (action === "replace") {
+      handleReplaceResponseInternal(response);
+    }
+  };
+
   const handleSuggestionClick = (templateName: string) => {
     if (isStreaming) return;
 
@@ -504,7 +747,13 @@ export default function PopupWindow() {
     if (template) {
       setCustomPrompt(`/${templateName}`);
       setShowSuggestions(false);
-      handleSend(template.prompt, template.action);
+      handleSend(
+        template.prompt,
+        template.action,
+        undefined,
+        undefined,
+        template.name,
+      );
     }
   };
 
@@ -562,6 +811,15 @@ export default function PopupWindow() {
       if (item.type.startsWith("image/")) {
         e.preventDefault(); // Prevent default paste behavior for images
 
+        // Read the image natively so it is encoded like a screenshot
+        try {
+          const screenshot = await pasteClipboardImage();
+          setScreenshots((prev) => [...prev, screenshot]);
+          return;
+        } catch (err) {
+          console.error("Failed to read clipboard image natively:", err);
+        }
+
         const file = item.getAsFile();
         if (!file) continue;
 
@@ -710,12 +968,103 @@ export default function PopupWindow() {
     }
   };
 
+  const handleAttachFiles = async () => {
+    try {
+      const selected = await open({ multiple: true, directory: false });
+      if (!selected) return;
+
+      const paths = Array.isArray(selected) ? selected : [selected];
+      for (const path of paths) {
+        try {
+          await attachFile(path);
+        } catch (err) {
+          setError(String(err));
+        }
+      }
+
+      // Images are attached as screenshots, documents as attachments
+      setScreenshots(await getScreenshots());
+      setAttachments(await listAttachments());
+    } catch (err) {
+      console.error("Failed to attach files:", err);
+    }
+  };
+
+  const handleToggleWindowPicker = async () => {
+    if (windowPicker) {
+      setWindowPicker(null);
+      return;
+    }
+
+    try {
+      setWindowPicker(await listWindows());
+    } catch (err) {
+      console.error("Failed to list windows:", err);
+      setError(String(err));
+    }
+  };
+
+  const handlePickWindow = async (windowId: number) => {
+    try {
+      const screenshot = await captureWindow(windowId);
+      setScreenshots((prev) => [...prev, screenshot]);
+      setWindowPicker(null);
+    } catch (err) {
+      console.error("Failed to capture window:", err);
+      setError(String(err));
+    }
+  };
+
+  const handleToggleHistoryPicker = async () => {
+    if (historyPicker) {
+      setHistoryPicker(null);
+      return;
+    }
+
+    try {
+      setHistoryPicker(await listScreenshotHistory());
+    } catch (err) {
+      console.error("Failed to list screenshot history:", err);
+      setError(String(err));
+    }
+  };
+
+  const handlePickSavedScreenshot = async (path: string) => {
+    try {
+      const screenshot = await reattachScreenshot(path);
+      setScreenshots((prev) => [...prev, screenshot]);
+      setHistoryPicker(null);
+    } catch (err) {
+      console.error("Failed to attach saved screenshot:", err);
+      setError(String(err));
+    }
+  };
+
+  const handleRemoveAttachment = async (index: number) => {
+    try {
+      await removeAttachment(index);
+      setAttachments((prev) => prev.filter((_, i) => i !== index));
+    } catch (err) {
+      console.error("Failed to remove attachment:", err);
+    }
+  };
+
+  const handleClearAttachments = async () => {
+    try {
+      await clearAttachments();
+      setAttachments([]);
+    } catch (err) {
+      console.error("Failed to clear attachments:", err);
+    }
+  };
+
   // Filter models based on screenshots presence
   const getAvailableModels = () => {
     if (!config) return [];
 
     // If there are screenshots, only show vision-capable models
-    if (screenshots.length > 0) {
+    // unless OCR can stand in for vision
+    if (screenshots.length > 0 && config.vision_fallback !== "ocr") {
       return config.models.filter((model) => model.supports_vision);
     }
 
@@ -843,6 +1192,70 @@ export default function PopupWindow() {
               </div>
             )}
           </div>
+          <button
+            className="pin-button"
+            onClick={handleAttachFiles}
+            title="Attach files"
+            disabled={isStreaming}
+            type="button"
+          >
+            <svg
+              width="14"
+              height="14"
+              viewBox="0 0 24 24"
+              fill="none"
+              stroke="currentColor"
+              strokeWidth="2"
+              strokeLinecap="round"
+              strokeLinejoin="round"
+            >
+              <path d="m21.44 11.05-9.19 9.19a6 6 0 0 1-8.49-8.49l8.57-8.57A4 4 0 1 1 18 8.84l-8.59 8.57a2 2 0 0 1-2.83-2.83l8.49-8.48" />
+            </svg>
+          </button>
+          <button
+            className="pin-button"
+            onClick={handleToggleWindowPicker}
+            title="Attach a window screenshot"
+            disabled={isStreaming}
+            type="button"
+          >
+            <svg
+              width="14"
+              height="14"
+              viewBox="0 0 24 24"
+              fill="none"
+              stroke="currentColor"
+              strokeWidth="2"
+              strokeLinecap="round"
+              strokeLinejoin="round"
+            >
+              <rect x="3" y="4" width="18" height="16" rx="2" />
+              <line x1="3" y1="9" x2="21" y2="9" />
+            </svg>
+          </button>
+          {config.screenshot?.save_to_disk && (
+            <button
+              className="pin-button"
+              onClick={handleToggleHistoryPicker}
+              title="Attach a saved screenshot"
+              disabled={isStreaming}
+              type="button"
+            >
+              <svg
+                width="14"
+                height="14"
+                viewBox="0 0 24 24"
+                fill="none"
+                stroke="currentColor"
+                strokeWidth="2"
+                strokeLinecap="round"
+                strokeLinejoin="round"
+              >
+                <circle cx="12" cy="12" r="9" />
+                <polyline points="12 7 12 12 15 14" />
+              </svg>
+            </button>
+          )}
           <button
             className={`pin-button ${isPinned ? "pinned" : ""}`}
             onClick={handlePinClick}
@@ -880,6 +1293,12 @@ export default function PopupWindow() {
               <span className="screenshots-count">
                 {screenshots.length} screenshot
                 {screenshots.length !== 1 ? "s" : ""}
+                {screenshotInfo.length > 0 &&
+                  ` · ${formatBytes(
+                    screenshotInfo.reduce((sum, info) => sum + info.bytes, 0),
+                  )} · ~${screenshotInfo
+                    .reduce((sum, info) => sum + info.tokens, 0)
+                    .toLocaleString()} tokens`}
               </span>
               <button
                 className="clear-screenshots-button"
@@ -918,6 +1337,118 @@ export default function PopupWindow() {
           </div>
         )}
 
+        {windowPicker && (
+          <div className="screenshots-container">
+            <div className="screenshots-header">
+              <span className="screenshots-count">
+                {windowPicker.length > 0
+                  ? "Pick a window to attach"
+                  : "No windows to capture"}
+              </span>
+              <button
+                className="clear-screenshots-button"
+                onClick={() => setWindowPicker(null)}
+                type="button"
+              >
+                Cancel
+              </button>
+            </div>
+            <div className="screenshots-grid">
+              {windowPicker.map((win) => (
+                <div
+                  key={win.id}
+                  className="screenshot-thumbnail"
+                  title={`${win.app_name} — ${win.title}`}
+                  onClick={() => handlePickWindow(win.id)}
+                >
+                  {win.thumbnail ? (
+                    <img src={win.thumbnail} alt={win.title} />
+                  ) : (
+                    <span className="window-title">{win.title}</span>
+                  )}
+                </div>
+              ))}
+            </div>
+          </div>
+        )}
+
+        {historyPicker && (
+          <div className="screenshots-container">
+            <div className="screenshots-header">
+              <span className="screenshots-count">
+                {historyPicker.length > 0
+                  ? "Pick a saved screenshot to attach"
+                  : "No saved screenshots"}
+              </span>
+              <button
+                className="clear-screenshots-button"
+                onClick={() => setHistoryPicker(null)}
+                type="button"
+              >
+                Cancel
+              </button>
+            </div>
+            <div className="screenshots-grid">
+              {historyPicker.map((saved) => (
+                <div
+                  key={saved.path}
+                  className="screenshot-thumbnail"
+                  title={`${new Date(saved.created_at).toLocaleString()} · ${formatBytes(saved.size)}`}
+                  onClick={() => handlePickSavedScreenshot(saved.path)}
+                >
+                  {saved.thumbnail ? (
+                    <img src={saved.thumbnail} alt={saved.name} />
+                  ) : (
+                    <span className="window-title">{saved.name}</span>
+                  )}
+                </div>
+              ))}
+            </div>
+          </div>
+        )}
+
+        {attachments.length > 0 && (
+          <div className="screenshots-container">
+            <div className="screenshots-header">
+              <span className="screenshots-count">
+                {attachments.length} file
+                {attachments.length !== 1 ? "s" : ""} · ~
+                {attachments
+                  .reduce((sum, a) => sum + a.tokens, 0)
+                  .toLocaleString()}{" "}
+                tokens
+              </span>
+              <button
+                className="clear-screenshots-button"
+                onClick={handleClearAttachments}
+                type="button"
+              >
+                Clear all
+              </button>
+            </div>
+            <div className="attachments-list">
+              {attachments.map((attachment, index) => (
+                <span
+                  key={index}
+                  className="attachment-chip"
+                  title={attachment.path}
+                >
+                  {attachment.name}
+                  {attachment.truncated ? " (truncated)" : ""}
+                  <button
+                    className="remove-attachment-button"
+                    onClick={() => handleRemoveAttachment(index)}
+                    title="Remove file"
+                    type="button"
+                  >
+                    ×
+                  </button>
+                </span>
+              ))}
+            </div>
+          </div>
+        )}
+
         {showSuggestions && getFilteredTemplates().length > 0 && (
           <div className="suggestions-menu" ref={suggestionsRef}>
             {getFilteredTemplates().map((template, index) => (
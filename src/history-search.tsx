@@ -0,0 +1,9 @@
+import React from "react";
+import ReactDOM from "react-dom/client";
+import HistorySearch from "./components/HistorySearch";
+
+ReactDOM.createRoot(document.getElementById("history-search-root")!).render(
+  <React.StrictMode>
+    <HistorySearch />
+  </React.StrictMode>,
+);
@@ -1,20 +1,48 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 
+// Marks drawn onto the screenshot, relative to the selected region
+type Annotation =
+  | {
+      type: "rect" | "blur" | "redact";
+      x: number;
+      y: number;
+      width: number;
+      height: number;
+    }
+  | {
+      type: "arrow";
+      from_x: number;
+      from_y: number;
+      to_x: number;
+      to_y: number;
+    }
+  | { type: "text"; x: number; y: number; text: string };
+
+type Tool = Annotation["type"];
+
 let isSelecting = false;
 let startX = 0;
 let startY = 0;
 let currentX = 0;
 let currentY = 0;
 
+// Annotation mode starts when the selection is finished with Shift held
+let isAnnotating = false;
+let tool: Tool = "rect";
+let annotations: Annotation[] = [];
+let draft: Annotation | null = null;
+
 const canvas = document.getElementById("canvas") as HTMLCanvasElement;
 const selectionBox = document.getElementById("selection-box") as HTMLDivElement;
 const selectionInfo = document.getElementById(
   "selection-info",
 ) as HTMLDivElement;
 const hint = document.getElementById("hint") as HTMLDivElement;
+const toolbar = document.getElementById("toolbar") as HTMLDivElement;
 
-if (!canvas || !selectionBox || !selectionInfo || !hint) {
+if (!canvas || !selectionBox || !selectionInfo || !hint || !toolbar) {
   console.error("Required DOM elements not found");
   throw new Error("Failed to initialize screenshot selector");
 }
@@ -30,8 +58,30 @@ if (ctx) {
   ctx.fillRect(0, 0, canvas.width, canvas.height);
 }
 
-// Mouse down - start selection
+// Show the screen as it was when the selector opened, so menus and
+// hover states stay visible while selecting
+async function loadFrozenFrame() {
+  try {
+    const frame = await invoke<string | null>("get_frozen_frame");
+    document.body.style.backgroundImage = frame ? `url(${frame})` : "none";
+  } catch (error) {
+    console.error("Failed to load frozen frame:", error);
+  }
+}
+
+loadFrozenFrame();
+listen("frozen-frame-updated", () => {
+  resetSelection();
+  loadFrozenFrame();
+});
+
+// Mouse down - start selection, or an annotation inside it
 canvas.addEventListener("mousedown", (e) => {
+  if (isAnnotating) {
+    startAnnotation(e.clientX, e.clientY);
+    return;
+  }
+
   isSelecting = true;
   startX = e.clientX;
   startY = e.clientY;
@@ -47,6 +97,10 @@ canvas.addEventListener("mousedown", (e) => {
 
 // Mouse move - update selection
 canvas.addEventListener("mousemove", (e) => {
+  if (draft) {
+    updateAnnotation(e.clientX, e.clientY);
+    return;
+  }
   if (!isSelecting) return;
 
   currentX = e.clientX;
@@ -56,15 +110,16 @@ canvas.addEventListener("mousemove", (e) => {
 });
 
 // Mouse up - complete selection
-canvas.addEventListener("mouseup", async () => {
+canvas.addEventListener("mouseup", async (e) => {
+  if (draft) {
+    finishAnnotation();
+    return;
+  }
   if (!isSelecting) return;
 
   isSelecting = false;
 
-  const x = Math.min(startX, currentX);
-  const y = Math.min(startY, currentY);
-  const width = Math.abs(currentX - startX);
-  const height = Math.abs(currentY - startY);
+  const { width, height } = selectionRect();
 
   // Minimum selection size
   if (width < 10 || height < 10) {
@@ -72,11 +127,24 @@ canvas.addEventListener("mouseup", async () => {
     return;
   }
 
+  // Shift keeps the selection open for annotating
+  if (e.shiftKey) {
+    startAnnotating();
+    return;
+  }
+
+  await captureSelection();
+});
+
+async function captureSelection() {
+  const { x, y, width, height } = selectionRect();
+
   try {
     // Hide all UI elements before capturing
     selectionBox.style.display = "none";
     selectionInfo.style.display = "none";
     hint.style.display = "none";
+    toolbar.style.display = "none";
     if (ctx) {
       ctx.clearRect(0, 0, canvas.width, canvas.height);
     }
@@ -84,25 +152,29 @@ canvas.addEventListener("mouseup", async () => {
     // Wait a bit for UI to hide completely
     await new Promise((resolve) => setTimeout(resolve, 50));
 
-    // Get the device pixel ratio from browser (works without special permissions)
-    const scaleFactor = window.devicePixelRatio || 1;
-
-    // Adjust coordinates for device pixel ratio
-    const adjustedX = Math.floor(x * scaleFactor);
-    const adjustedY = Math.floor(y * scaleFactor);
-    const adjustedWidth = Math.floor(width * scaleFactor);
-    const adjustedHeight = Math.floor(height * scaleFactor);
+    // The selector is reused for attaching, scrolling captures and copying text
+    const mode = await invoke<string>("get_selector_mode");
 
-    // Capture the region
-    await invoke("capture_screenshot_region", {
-      x: adjustedX,
-      y: adjustedY,
-      width: adjustedWidth,
-      height: adjustedHeight,
-    });
+    // Coordinates are logical pixels; the backend applies the monitor's scale factor
+    if (mode === "ocr") {
+      // Copy the region's text to the clipboard without opening the popup
+      await invoke("capture_region_text", { x, y, width, height });
+    } else if (mode === "scroll") {
+      // The backend closes this window, scrolls and opens the popup itself
+      await invoke("capture_scrolling_region", { x, y, width, height });
+      return;
+    } else {
+      await invoke("capture_screenshot_region", {
+        x,
+        y,
+        width,
+        height,
+        annotations,
+      });
 
-    // Show the popup window with the captured screenshot
-    await invoke("show_popup_window");
+      // Show the popup window with the captured screenshot
+      await invoke("show_popup_window");
+    }
 
     // Close the selector window
     await getCurrentWindow().close();
@@ -111,20 +183,188 @@ canvas.addEventListener("mouseup", async () => {
     alert("截图失败: " + error);
     resetSelection();
   }
-});
+}
 
-// ESC key - cancel
+// ESC key - cancel, Enter - finish annotating
 document.addEventListener("keydown", async (e) => {
   if (e.key === "Escape") {
     await getCurrentWindow().close();
+  } else if (e.key === "Enter" && isAnnotating) {
+    await captureSelection();
+  } else if ((e.ctrlKey || e.metaKey) && e.key === "z" && isAnnotating) {
+    annotations.pop();
+    updateSelection();
+  }
+});
+
+toolbar.addEventListener("click", async (e) => {
+  const button = (e.target as HTMLElement).closest("button");
+  if (!button) return;
+
+  const action = button.dataset.action;
+  if (action === "undo") {
+    annotations.pop();
+    updateSelection();
+  } else if (action === "done") {
+    await captureSelection();
+  } else if (button.dataset.tool) {
+    tool = button.dataset.tool as Tool;
+    toolbar
+      .querySelectorAll("button[data-tool]")
+      .forEach((b) => b.classList.toggle("active", b === button));
   }
 });
 
+function selectionRect() {
+  return {
+    x: Math.min(startX, currentX),
+    y: Math.min(startY, currentY),
+    width: Math.abs(currentX - startX),
+    height: Math.abs(currentY - startY),
+  };
+}
+
+function startAnnotating() {
+  isAnnotating = true;
+  annotations = [];
+
+  const { x, y, height } = selectionRect();
+  toolbar.style.display = "flex";
+  toolbar.style.left = `${x}px`;
+  toolbar.style.top = `${Math.min(y + height + 8, window.innerHeight - 44)}px`;
+}
+
+function startAnnotation(clientX: number, clientY: number) {
+  const { x, y } = selectionRect();
+  const px = clientX - x;
+  const py = clientY - y;
+
+  if (tool === "text") {
+    const text = window.prompt("Text");
+    if (text) {
+      annotations.push({ type: "text", x: px, y: py, text });
+      updateSelection();
+    }
+    return;
+  }
+
+  draft =
+    tool === "arrow"
+      ? { type: "arrow", from_x: px, from_y: py, to_x: px, to_y: py }
+      : { type: tool, x: px, y: py, width: 0, height: 0 };
+}
+
+function updateAnnotation(clientX: number, clientY: number) {
+  if (!draft) return;
+
+  const { x, y } = selectionRect();
+  if (draft.type === "arrow") {
+    draft.to_x = clientX - x;
+    draft.to_y = clientY - y;
+  } else if (draft.type !== "text") {
+    draft.width = clientX - x - draft.x;
+    draft.height = clientY - y - draft.y;
+  }
+  updateSelection();
+}
+
+function finishAnnotation() {
+  if (!draft) return;
+
+  // Normalize boxes dragged up or left, and drop accidental clicks
+  if (draft.type === "arrow") {
+    const length = Math.hypot(
+      draft.to_x - draft.from_x,
+      draft.to_y - draft.from_y,
+    );
+    if (length >= 5) annotations.push(draft);
+  } else if (draft.type !== "text") {
+    const box = {
+      ...draft,
+      x: Math.min(draft.x, draft.x + draft.width),
+      y: Math.min(draft.y, draft.y + draft.height),
+      width: Math.abs(draft.width),
+      height: Math.abs(draft.height),
+    };
+    if (box.width >= 3 && box.height >= 3) annotations.push(box);
+  }
+
+  draft = null;
+  updateSelection();
+}
+
+// Previews the annotations; the backend draws the final versions
+function drawAnnotations(originX: number, originY: number) {
+  if (!ctx) return;
+
+  for (const annotation of draft ? [...annotations, draft] : annotations) {
+    ctx.save();
+    ctx.translate(originX, originY);
+    ctx.strokeStyle = "#ff3b30";
+    ctx.fillStyle = "#ff3b30";
+    ctx.lineWidth = 3;
+
+    switch (annotation.type) {
+      case "rect":
+        ctx.strokeRect(
+          annotation.x,
+          annotation.y,
+          annotation.width,
+          annotation.height,
+        );
+        break;
+      case "blur":
+        ctx.fillStyle = "rgba(128, 128, 128, 0.7)";
+        ctx.fillRect(
+          annotation.x,
+          annotation.y,
+          annotation.width,
+          annotation.height,
+        );
+        break;
+      case "redact":
+        ctx.fillStyle = "#000";
+        ctx.fillRect(
+          annotation.x,
+          annotation.y,
+          annotation.width,
+          annotation.height,
+        );
+        break;
+      case "arrow": {
+        const angle = Math.atan2(
+          annotation.to_y - annotation.from_y,
+          annotation.to_x - annotation.from_x,
+        );
+        ctx.beginPath();
+        ctx.moveTo(annotation.from_x, annotation.from_y);
+        ctx.lineTo(annotation.to_x, annotation.to_y);
+        ctx.stroke();
+        ctx.beginPath();
+        ctx.moveTo(annotation.to_x, annotation.to_y);
+        ctx.lineTo(
+          annotation.to_x - 12 * Math.cos(angle - Math.PI / 6),
+          annotation.to_y - 12 * Math.sin(angle - Math.PI / 6),
+        );
+        ctx.lineTo(
+          annotation.to_x - 12 * Math.cos(angle + Math.PI / 6),
+          annotation.to_y - 12 * Math.sin(angle + Math.PI / 6),
+        );
+        ctx.fill();
+        break;
+      }
+      case "text":
+        ctx.font = "bold 16px monospace";
+        ctx.textBaseline = "top";
+        ctx.fillText(annotation.text, annotation.x, annotation.y);
+        break;
+    }
+    ctx.restore();
+  }
+}
+
 function updateSelection() {
-  const x = Math.min(startX, currentX);
-  const y = Math.min(startY, currentY);
-  const width = Math.abs(currentX - startX);
-  const height = Math.abs(currentY - startY);
+  const { x, y, width, height } = selectionRect();
 
   selectionBox.style.left = `${x}px`;
   selectionBox.style.top = `${y}px`;
@@ -141,10 +381,15 @@ function updateSelection() {
     ctx.fillStyle = "rgba(0, 0, 0, 0.3)";
     ctx.fillRect(0, 0, canvas.width, canvas.height);
     ctx.clearRect(x, y, width, height);
+    drawAnnotations(x, y);
   }
 }
 
 function resetSelection() {
+  isAnnotating = false;
+  annotations = [];
+  draft = null;
+  toolbar.style.display = "none";
   selectionBox.style.display = "none";
   selectionInfo.style.display = "none";
   hint.style.display = "block";
@@ -1,9 +1,11 @@
 import { invoke, Channel } from "@tauri-apps/api/core";
+import type { Attachment } from "../types";
 
 export interface StreamCallbacks {
   onChunk: (content: string) => void;
   onError: (error: string) => void;
-  onDone: () => void;
+  // Receives the history conversation id, null when it wasn't recorded
+  onDone: (conversationId: number | null) => void;
 }
 
 export type MessageContentPart =
@@ -17,12 +19,19 @@ export interface Message {
   content: MessageContent;
 }
 
+export async function cancelAiResponse(): Promise<void> {
+  await invoke("cancel_ai_response");
+}
+
 export async function streamAiResponse(
   baseUrl: string,
   apiKey: string,
   modelName: string,
+  template: string | null,
+  conversationId: number | null,
   messages: Message[],
   screenshots: string[],
+  attachments: Attachment[],
   callbacks: StreamCallbacks,
 ): Promise<void> {
   const { onChunk, onError, onDone } = callbacks;
@@ -37,17 +46,20 @@ export async function streamAiResponse(
     };
 
     // Invoke the Rust command with the channel
-    await invoke("stream_ai_response", {
+    const recordedId = await invoke<number | null>("stream_ai_response", {
       baseUrl,
       apiKey,
       modelName,
+      template,
+      conversationId,
       messages,
       screenshots,
+      attachments,
       channel,
     });
 
     // Stream completed successfully
-    onDone();
+    onDone(recordedId);
   } catch (error) {
     const errorMessage =
       error instanceof Error ? error.message : "Unknown error occurred";
@@ -5,6 +5,106 @@ export interface AppConfig {
   selected_model_index: number;
   popup_width: number;
   max_popup_height: number;
+  hotkeys_paused?: boolean;
+  include_window_context?: boolean;
+  screenshot?: ScreenshotConfig;
+  ocr?: OcrConfig;
+  vision_fallback?: "block" | "route" | "ocr";
+  vision_model?: string;
+  redaction?: RedactionConfig;
+  save_history?: boolean;
+  history_retention_count?: number;
+}
+
+export interface RedactionConfig {
+  enabled: boolean;
+  emails: boolean;
+  api_keys: boolean;
+  card_numbers: boolean;
+}
+
+export interface OcrConfig {
+  model_dir: string;
+  hotkey?: string | null;
+}
+
+export interface ScreenshotConfig {
+  max_dimension: number;
+  format: "png" | "jpeg" | "webp";
+  quality: number;
+  save_to_disk?: boolean;
+  save_folder?: string;
+  retention_count?: number;
+  scroll_tile_height?: number;
+}
+
+export interface SavedScreenshot {
+  name: string;
+  path: string;
+  created_at: number;
+  size: number;
+  thumbnail: string | null;
+}
+
+export interface ConversationSummary {
+  id: number;
+  title: string;
+  created_at: number;
+  updated_at: number;
+  exchange_count: number;
+  model: string;
+  template: string | null;
+}
+
+export interface Exchange {
+  id: number;
+  source: "popup" | "background";
+  template: string | null;
+  model: string;
+  input: string;
+  // Text captured from the focused app, sent ahead of the prompt
+  captured_text: string;
+  output: string;
+  screenshots: string[];
+  started_at: number;
+  finished_at: number;
+}
+
+export interface Conversation {
+  id: number;
+  title: string;
+  created_at: number;
+  updated_at: number;
+  exchanges: Exchange[];
+}
+
+export interface HistorySearchQuery {
+  query: string;
+  template?: string | null;
+  model?: string | null;
+  // Milliseconds since the Unix epoch, `to` is exclusive
+  from?: number | null;
+  to?: number | null;
+}
+
+export interface SearchHit {
+  conversation_id: number;
+  exchange_id: number;
+  title: string;
+  template: string | null;
+  model: string;
+  started_at: number;
+  // Matched terms are wrapped in \u0002 and \u0003
+  input_snippet: string;
+  output_snippet: string;
+  score: number;
+}
+
+export interface ScreenshotInfo {
+  bytes: number;
+  width: number;
+  height: number;
+  tokens: number;
 }
 
 export interface ModelConfig {
@@ -22,9 +122,41 @@ export interface QuestionTemplate {
   action: "none" | "copy" | "replace";
   hotkey?: string | null;
   background_mode?: boolean;
+  hold_mode?: boolean;
+  include_apps?: string[];
+  exclude_apps?: string[];
+  input_source?: "selection" | "selection_or_clipboard" | "clipboard" | "ask";
+  input_format?: "plain" | "rich";
+}
+
+export interface PromptContext {
+  prompt: string;
+  system_message: string | null;
+}
+
+export interface Attachment {
+  name: string;
+  path: string;
+  kind: "text" | "pdf";
+  content: string;
+  size: number;
+  truncated: boolean;
+  tokens: number;
 }
 
 export interface HotkeyConfig {
   popup_hotkey: string;
   screenshot_hotkey?: string;
+  popup_hold_mode?: boolean;
+  screenshot_mode?: "region" | "window";
+  clipboard_image_hotkey?: string | null;
+  scroll_capture_hotkey?: string | null;
+  history_search_hotkey?: string | null;
+}
+
+export interface WindowInfo {
+  id: number;
+  title: string;
+  app_name: string;
+  thumbnail: string | null;
 }
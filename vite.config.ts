@@ -16,6 +16,7 @@ export default defineConfig(async () => ({
         main: resolve(__dirname, "index.html"),
         popup: resolve(__dirname, "popup.html"),
         screenshotSelector: resolve(__dirname, "screenshot-selector.html"),
+        historySearch: resolve(__dirname, "history-search.html"),
       },
     },
   },